@@ -0,0 +1,227 @@
+use crate::abi::{AbiError, ParamType, Token};
+use ethereum_types::{Address, U256};
+
+/// Decodes the data as the items of a tuple of the given types, e.g. the
+/// outputs of a function. Offsets are relative to the start of the data.
+pub fn decode(
+    types: &[ParamType],
+    data: &[u8],
+) -> Result<Vec<Token>, AbiError> {
+    // Offsets may point to the same data, so that nested arrays could decode
+    // to far more values than the data holds. A valid encoding stores each
+    // value in its own words, so the decoded values may not take more bytes
+    // than the data in total.
+    let mut budget = data.len();
+    decode_list(types, data, &mut budget)
+}
+
+fn decode_list(
+    types: &[ParamType],
+    data: &[u8],
+    budget: &mut usize,
+) -> Result<Vec<Token>, AbiError> {
+    let mut tokens = Vec::with_capacity(types.len());
+    let mut pos = 0;
+    for kind in types {
+        let token = if kind.is_dynamic() {
+            let offset = read_len(data, pos)?;
+            let tail = data.get(offset..).ok_or(AbiError::InvalidData)?;
+            decode_token(kind, tail, budget)?
+        } else {
+            let head = data.get(pos..).ok_or(AbiError::InvalidData)?;
+            decode_token(kind, head, budget)?
+        };
+        tokens.push(token);
+        pos += kind.head_size();
+    }
+    Ok(tokens)
+}
+
+/// Decodes a value starting at the beginning of data, and charges the bytes
+/// it takes to the budget.
+fn decode_token(
+    kind: &ParamType,
+    data: &[u8],
+    budget: &mut usize,
+) -> Result<Token, AbiError> {
+    let size = match kind {
+        ParamType::Bytes | ParamType::String => {
+            32 + read_bytes(data)?.len().div_ceil(32) * 32
+        }
+        // Values of zero size are charged a word, as they could otherwise be
+        // repeated for free.
+        ParamType::FixedArray(..) | ParamType::Tuple(_) => {
+            if kind.head_size() == 0 {
+                32
+            } else {
+                0
+            }
+        }
+        _ => 32,
+    };
+    *budget = budget.checked_sub(size).ok_or(AbiError::InvalidData)?;
+    let token = match kind {
+        ParamType::Address => {
+            let word = read_word(data, 0)?;
+            if word[..12] != [0u8; 12] {
+                return Err(AbiError::InvalidData);
+            }
+            Token::Address(Address::from_slice(&word[12..]))
+        }
+        ParamType::Bool => match U256::from_big_endian(read_word(data, 0)?) {
+            value if value.is_zero() => Token::Bool(false),
+            value if value == U256::one() => Token::Bool(true),
+            _ => return Err(AbiError::InvalidData),
+        },
+        ParamType::Uint(_) | ParamType::Int(_) => {
+            let value = U256::from_big_endian(read_word(data, 0)?);
+            let token = match kind {
+                ParamType::Uint(_) => Token::Uint(value),
+                _ => Token::Int(value),
+            };
+            if !token.matches(kind) {
+                return Err(AbiError::InvalidData);
+            }
+            token
+        }
+        ParamType::FixedBytes(len) => {
+            let word = read_word(data, 0)?;
+            if word[*len..].iter().any(|byte| *byte != 0) {
+                return Err(AbiError::InvalidData);
+            }
+            Token::FixedBytes(word[..*len].to_vec())
+        }
+        ParamType::Bytes => Token::Bytes(read_bytes(data)?.to_vec()),
+        ParamType::String => {
+            let value = String::from_utf8(read_bytes(data)?.to_vec())
+                .map_err(|_| AbiError::InvalidData)?;
+            Token::String(value)
+        }
+        ParamType::Array(elem) => {
+            let len = read_len(data, 0)?;
+            let items = &data[32..];
+            // Each item takes at least a word of the budget, reject lengths
+            // that cannot fit before allocating for them.
+            if len > *budget / 32 {
+                return Err(AbiError::InvalidData);
+            }
+            let types = vec![elem.as_ref().clone(); len];
+            Token::Array(decode_list(&types, items, budget)?)
+        }
+        ParamType::FixedArray(elem, len) => {
+            let types = vec![elem.as_ref().clone(); *len];
+            Token::FixedArray(decode_list(&types, data, budget)?)
+        }
+        ParamType::Tuple(types) => {
+            Token::Tuple(decode_list(types, data, budget)?)
+        }
+    };
+    Ok(token)
+}
+
+fn read_word(data: &[u8], pos: usize) -> Result<&[u8], AbiError> {
+    let end = pos.checked_add(32).ok_or(AbiError::InvalidData)?;
+    data.get(pos..end).ok_or(AbiError::InvalidData)
+}
+
+/// Reads a word holding a length or an offset.
+fn read_len(data: &[u8], pos: usize) -> Result<usize, AbiError> {
+    let value = U256::from_big_endian(read_word(data, pos)?);
+    if value > U256::from(data.len()) {
+        return Err(AbiError::InvalidData);
+    }
+    Ok(value.as_usize())
+}
+
+/// Reads a byte array prefixed by its length.
+fn read_bytes(data: &[u8]) -> Result<&[u8], AbiError> {
+    let len = read_len(data, 0)?;
+    data.get(32..32 + len).ok_or(AbiError::InvalidData)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::encode;
+
+    fn parse(types: &[&str]) -> Vec<ParamType> {
+        types.iter().map(|s| ParamType::parse(s).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_decode_roundtrip() {
+        let types = parse(&[
+            "uint256",
+            "int8",
+            "uint32[]",
+            "bytes10",
+            "bytes",
+            "(address,string[2])",
+            "(bool,uint8)[2]",
+        ]);
+        let tokens = vec![
+            Token::Uint(0x123.into()),
+            Token::from(-2),
+            Token::Array(vec![Token::Uint(0x456.into())]),
+            Token::FixedBytes(b"1234567890".to_vec()),
+            Token::Bytes(b"Hello, world!".to_vec()),
+            Token::Tuple(vec![
+                Address::repeat_byte(1).into(),
+                Token::FixedArray(vec!["a".into(), "".into()]),
+            ]),
+            Token::FixedArray(vec![
+                Token::Tuple(vec![true.into(), Token::Uint(1.into())]),
+                Token::Tuple(vec![false.into(), Token::Uint(2.into())]),
+            ]),
+        ];
+        let data = encode(&tokens);
+        assert_eq!(decode(&types, &data), Ok(tokens));
+    }
+
+    #[test]
+    fn test_decode_aliasing() {
+        let word = |value: u64| Token::from(value);
+        let types = parse(&["uint256[][]"]);
+        // An outer array of n items whose offsets all point to the same
+        // inner array of n words.
+        let n = 100;
+        let mut words = vec![word(32), word(n)];
+        words.extend((0..n).map(|_| word(n * 32)));
+        words.push(word(n));
+        words.extend((0..n).map(word));
+        let res = decode(&types, &encode(&words));
+        assert_eq!(res, Err(AbiError::InvalidData));
+        // The same values encoded without aliasing fit in the data.
+        let inner =
+            Token::Array((0..n).map(|i| Token::Uint(i.into())).collect());
+        let tokens = vec![Token::Array(vec![inner; n as usize])];
+        assert_eq!(decode(&types, &encode(&tokens)), Ok(tokens));
+        // Empty tuples take no space in the data.
+        let types = parse(&["()[]"]);
+        let res = decode(&types, &encode(&[word(32), word(64)]));
+        assert_eq!(res, Err(AbiError::InvalidData));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let word = |value: u64| Token::from(value);
+        let types = parse(&["bool"]);
+        assert_eq!(
+            decode(&types, &encode(&[word(2)])),
+            Err(AbiError::InvalidData)
+        );
+        let types = parse(&["uint8"]);
+        let res = decode(&types, &encode(&[word(256)]));
+        assert_eq!(res, Err(AbiError::InvalidData));
+        let types = parse(&["uint256", "uint256"]);
+        let res = decode(&types, &encode(&[word(1)]));
+        assert_eq!(res, Err(AbiError::InvalidData));
+        // An array claiming more items than the data holds.
+        let types = parse(&["uint256[]"]);
+        let res = decode(&types, &encode(&[word(32), word(1000)]));
+        assert_eq!(res, Err(AbiError::InvalidData));
+        let types = parse(&["bytes"]);
+        let res = decode(&types, &encode(&[word(32), word(33), word(0)]));
+        assert_eq!(res, Err(AbiError::InvalidData));
+    }
+}
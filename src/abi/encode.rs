@@ -0,0 +1,149 @@
+use crate::abi::Token;
+
+/// Encodes the values as the items of a tuple, e.g. function arguments.
+pub fn encode(tokens: &[Token]) -> Vec<u8> {
+    let head_size: usize = tokens.iter().map(head_size).sum();
+    let mut head = Vec::with_capacity(head_size);
+    let mut tail = Vec::new();
+    for token in tokens {
+        if token.is_dynamic() {
+            head.extend_from_slice(&word(head_size + tail.len()));
+            tail.extend(encode_token(token));
+        } else {
+            head.extend(encode_token(token));
+        }
+    }
+    head.extend(tail);
+    head
+}
+
+/// Returns the size of the value in the head of the enclosing list.
+fn head_size(token: &Token) -> usize {
+    match token {
+        _ if token.is_dynamic() => 32,
+        Token::FixedArray(items) | Token::Tuple(items) => {
+            items.iter().map(head_size).sum()
+        }
+        _ => 32,
+    }
+}
+
+fn encode_token(token: &Token) -> Vec<u8> {
+    match token {
+        Token::Address(address) => {
+            let mut data = vec![0u8; 12];
+            data.extend_from_slice(address.as_bytes());
+            data
+        }
+        Token::Bool(value) => word(*value as usize).to_vec(),
+        Token::Uint(value) | Token::Int(value) => {
+            let mut data = vec![0u8; 32];
+            value.to_big_endian(&mut data);
+            data
+        }
+        Token::FixedBytes(data) => pad_right(data),
+        Token::Bytes(data) => {
+            let mut out = word(data.len()).to_vec();
+            out.extend(pad_right(data));
+            out
+        }
+        Token::String(value) => {
+            encode_token(&Token::Bytes(value.as_bytes().to_vec()))
+        }
+        Token::Array(items) => {
+            let mut out = word(items.len()).to_vec();
+            out.extend(encode(items));
+            out
+        }
+        Token::FixedArray(items) | Token::Tuple(items) => encode(items),
+    }
+}
+
+fn word(value: usize) -> [u8; 32] {
+    let mut data = [0u8; 32];
+    data[24..].copy_from_slice(&(value as u64).to_be_bytes());
+    data
+}
+
+/// Returns the data padded with zeros to a multiple of 32 bytes.
+fn pad_right(data: &[u8]) -> Vec<u8> {
+    let mut out = data.to_vec();
+    out.resize(data.len().div_ceil(32) * 32, 0);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_static() {
+        let tokens = [Token::from(42), Token::from(-1), Token::from(true)];
+        let expected = concat!(
+            "000000000000000000000000000000000000000000000000000000000000002a",
+            "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            "0000000000000000000000000000000000000000000000000000000000000001"
+        );
+        assert_eq!(hex::encode(encode(&tokens)), expected);
+    }
+
+    #[test]
+    fn test_encode_dynamic() {
+        // Example of the Solidity ABI specification for
+        // f(uint256,uint32[],bytes10,bytes).
+        let tokens = [
+            Token::from(0x123),
+            Token::Array(vec![0x456.into(), 0x789.into()]),
+            Token::FixedBytes(b"1234567890".to_vec()),
+            Token::Bytes(b"Hello, world!".to_vec()),
+        ];
+        let expected = concat!(
+            "0000000000000000000000000000000000000000000000000000000000000123",
+            "0000000000000000000000000000000000000000000000000000000000000080",
+            "3132333435363738393000000000000000000000000000000000000000000000",
+            "00000000000000000000000000000000000000000000000000000000000000e0",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "0000000000000000000000000000000000000000000000000000000000000456",
+            "0000000000000000000000000000000000000000000000000000000000000789",
+            "000000000000000000000000000000000000000000000000000000000000000d",
+            "48656c6c6f2c20776f726c642100000000000000000000000000000000000000"
+        );
+        assert_eq!(hex::encode(encode(&tokens)), expected);
+    }
+
+    #[test]
+    fn test_encode_nested() {
+        // Example of the Solidity ABI specification for
+        // g(uint256[][],string[]) with ([[1, 2], [3]], ["one", "two", "three"]).
+        let tokens = [
+            Token::Array(vec![
+                Token::Array(vec![1.into(), 2.into()]),
+                Token::Array(vec![3.into()]),
+            ]),
+            Token::Array(vec!["one".into(), "two".into(), "three".into()]),
+        ];
+        let expected = concat!(
+            "0000000000000000000000000000000000000000000000000000000000000040",
+            "0000000000000000000000000000000000000000000000000000000000000140",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "0000000000000000000000000000000000000000000000000000000000000040",
+            "00000000000000000000000000000000000000000000000000000000000000a0",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "0000000000000000000000000000000000000000000000000000000000000060",
+            "00000000000000000000000000000000000000000000000000000000000000a0",
+            "00000000000000000000000000000000000000000000000000000000000000e0",
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "6f6e650000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "74776f0000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000005",
+            "7468726565000000000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(hex::encode(encode(&tokens)), expected);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::abi::param::join;
+use crate::abi::{decode, AbiError, ParamType, Token};
+use crate::types::Log;
+use ethereum_types::H256;
+use sha3::{Digest, Keccak256};
+
+/// A contract event, as described by a JSON ABI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub name: String,
+    pub inputs: Vec<EventParam>,
+    /// Whether the event is emitted without its signature as first topic.
+    pub anonymous: bool,
+}
+
+/// A named input of an event, stored in a topic if indexed or in the log
+/// data otherwise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventParam {
+    pub name: String,
+    pub kind: ParamType,
+    pub indexed: bool,
+}
+
+/// A log decoded against the event that emitted it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedLog {
+    pub name: String,
+    /// The inputs of the event with their values, in declaration order.
+    pub params: Vec<(String, Token)>,
+}
+
+impl Event {
+    /// Returns the canonical signature, e.g. "Add(uint256,uint256)".
+    pub fn signature(&self) -> String {
+        let types: Vec<_> =
+            self.inputs.iter().map(|p| p.kind.clone()).collect();
+        format!("{}({})", self.name, join(&types))
+    }
+
+    /// Returns the hash of the signature, which is the first topic of the
+    /// logs of non-anonymous events.
+    pub fn topic(&self) -> H256 {
+        H256::from_slice(&Keccak256::digest(self.signature().as_bytes()))
+    }
+
+    /// Decodes a log emitted by the event. Indexed values of reference types
+    /// are only stored as their hash, they decode to 32 fixed bytes.
+    pub fn decode_log(&self, log: &Log) -> Result<DecodedLog, AbiError> {
+        let topics = if self.anonymous {
+            &log.topics[..]
+        } else {
+            match log.topics.split_first() {
+                Some((topic0, rest)) if *topic0 == self.topic() => rest,
+                _ => return Err(AbiError::InvalidData),
+            }
+        };
+        let indexed = self.inputs.iter().filter(|p| p.indexed).count();
+        if topics.len() != indexed {
+            return Err(AbiError::InvalidData);
+        }
+        let types: Vec<_> = self
+            .inputs
+            .iter()
+            .filter(|p| !p.indexed)
+            .map(|p| p.kind.clone())
+            .collect();
+        let mut values = decode(&types, &log.data)?.into_iter();
+        let mut topics = topics.iter();
+        let mut params = Vec::with_capacity(self.inputs.len());
+        for param in &self.inputs {
+            let value = match (param.indexed, topics.next()) {
+                (true, Some(topic)) => decode_topic(&param.kind, topic)?,
+                _ => values.next().ok_or(AbiError::InvalidData)?,
+            };
+            params.push((param.name.clone(), value));
+        }
+        Ok(DecodedLog {
+            name: self.name.clone(),
+            params,
+        })
+    }
+}
+
+fn decode_topic(kind: &ParamType, topic: &H256) -> Result<Token, AbiError> {
+    match kind {
+        ParamType::Bytes
+        | ParamType::String
+        | ParamType::Array(_)
+        | ParamType::FixedArray(..)
+        | ParamType::Tuple(_) => {
+            Ok(Token::FixedBytes(topic.as_bytes().to_vec()))
+        }
+        _ => {
+            Ok(decode(std::slice::from_ref(kind), topic.as_bytes())?.remove(0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::encode;
+    use ethereum_types::Address;
+
+    fn param(name: &str, kind: &str, indexed: bool) -> EventParam {
+        EventParam {
+            name: name.to_string(),
+            kind: ParamType::parse(kind).unwrap(),
+            indexed,
+        }
+    }
+
+    fn transfer() -> Event {
+        Event {
+            name: "Transfer".to_string(),
+            inputs: vec![
+                param("from", "address", true),
+                param("to", "address", true),
+                param("value", "uint256", false),
+            ],
+            anonymous: false,
+        }
+    }
+
+    #[test]
+    fn test_topic() {
+        assert_eq!(
+            hex::encode(transfer().topic()),
+            "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+        );
+    }
+
+    #[test]
+    fn test_decode_log() {
+        let event = transfer();
+        let from = Address::repeat_byte(1);
+        let to = Address::repeat_byte(2);
+        let mut log = Log {
+            topics: vec![event.topic(), from.into(), to.into()],
+            data: encode(&[Token::from(1000)]),
+        };
+        let decoded = event.decode_log(&log).unwrap();
+        assert_eq!(decoded.name, "Transfer");
+        assert_eq!(
+            decoded.params,
+            vec![
+                ("from".to_string(), Token::Address(from)),
+                ("to".to_string(), Token::Address(to)),
+                ("value".to_string(), Token::Uint(1000.into())),
+            ]
+        );
+        log.topics.pop();
+        assert_eq!(event.decode_log(&log), Err(AbiError::InvalidData));
+        log.topics = vec![H256::zero(), from.into(), to.into()];
+        assert_eq!(event.decode_log(&log), Err(AbiError::InvalidData));
+    }
+
+    #[test]
+    fn test_decode_log_anonymous() {
+        let event = Event {
+            name: "Note".to_string(),
+            inputs: vec![
+                param("memo", "string", true),
+                param("ok", "bool", false),
+            ],
+            anonymous: true,
+        };
+        let hash = H256::repeat_byte(0xab);
+        let log = Log {
+            topics: vec![hash],
+            data: encode(&[true.into()]),
+        };
+        let decoded = event.decode_log(&log).unwrap();
+        assert_eq!(
+            decoded.params,
+            vec![
+                ("memo".to_string(), Token::from(hash)),
+                ("ok".to_string(), Token::Bool(true)),
+            ]
+        );
+    }
+}
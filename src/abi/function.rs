@@ -0,0 +1,186 @@
+use crate::abi::param::{join, split_list};
+use crate::abi::{decode, encode, AbiError, Param, ParamType, Token};
+use sha3::{Digest, Keccak256};
+
+/// A contract function, as described by a JSON ABI or a signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub inputs: Vec<Param>,
+    pub outputs: Vec<Param>,
+}
+
+impl Function {
+    /// Parses a signature such as "setX(uint256)", optionally followed by
+    /// the output types as in "getX()(uint256)".
+    pub fn parse(signature: &str) -> Result<Self, AbiError> {
+        let invalid = || AbiError::InvalidSignature(signature.to_string());
+        let signature = signature.trim();
+        let open = signature.find('(').ok_or_else(invalid)?;
+        let name = &signature[..open];
+        if name.is_empty()
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(invalid());
+        }
+        let (inputs, rest) =
+            split_group(&signature[open..]).ok_or_else(invalid)?;
+        let outputs = if rest.is_empty() {
+            Vec::new()
+        } else {
+            let (outputs, rest) = split_group(rest).ok_or_else(invalid)?;
+            if !rest.is_empty() {
+                return Err(invalid());
+            }
+            parse_params(outputs)?
+        };
+        Ok(Function {
+            name: name.to_string(),
+            inputs: parse_params(inputs)?,
+            outputs,
+        })
+    }
+
+    /// Returns the canonical signature, e.g. "setX(uint256)".
+    pub fn signature(&self) -> String {
+        format!("{}({})", self.name, join(&param_types(&self.inputs)))
+    }
+
+    /// Returns the first 4 bytes of the hash of the signature, which select
+    /// the function in the calldata.
+    pub fn selector(&self) -> [u8; 4] {
+        let hash = Keccak256::digest(self.signature().as_bytes());
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+
+    /// Returns the calldata calling the function with the given arguments.
+    pub fn encode_input(&self, args: &[Token]) -> Result<Vec<u8>, AbiError> {
+        let types = param_types(&self.inputs);
+        if args.len() != types.len()
+            || args.iter().zip(&types).any(|(arg, t)| !arg.matches(t))
+        {
+            return Err(AbiError::ArgumentMismatch);
+        }
+        let args: Vec<_> = args.iter().zip(&types).map(convert).collect();
+        let mut data = self.selector().to_vec();
+        data.extend(encode(&args));
+        Ok(data)
+    }
+
+    /// Decodes the arguments of calldata calling the function.
+    pub fn decode_input(&self, data: &[u8]) -> Result<Vec<Token>, AbiError> {
+        match data.strip_prefix(&self.selector()) {
+            Some(args) => decode(&param_types(&self.inputs), args),
+            None => Err(AbiError::InvalidData),
+        }
+    }
+
+    /// Decodes the data returned by the function.
+    pub fn decode_output(&self, data: &[u8]) -> Result<Vec<Token>, AbiError> {
+        decode(&param_types(&self.outputs), data)
+    }
+}
+
+/// Returns the types of the parameters.
+pub fn param_types(params: &[Param]) -> Vec<ParamType> {
+    params.iter().map(|param| param.kind.clone()).collect()
+}
+
+/// Converts an integer to the signedness of the type it is encoded as, so
+/// that encoding a matching value never depends on it.
+fn convert((token, kind): (&Token, &ParamType)) -> Token {
+    match (token, kind) {
+        (Token::Int(value), ParamType::Uint(_)) => Token::Uint(*value),
+        (Token::Uint(value), ParamType::Int(_)) => Token::Int(*value),
+        (Token::Array(items), ParamType::Array(elem)) => Token::Array(
+            items.iter().map(|item| convert((item, elem))).collect(),
+        ),
+        (Token::FixedArray(items), ParamType::FixedArray(elem, _)) => {
+            let items = items.iter().map(|item| convert((item, elem)));
+            Token::FixedArray(items.collect())
+        }
+        (Token::Tuple(items), ParamType::Tuple(types)) => {
+            Token::Tuple(items.iter().zip(types).map(convert).collect())
+        }
+        _ => token.clone(),
+    }
+}
+
+/// Splits "(...)rest" into the content of the parentheses and the rest.
+fn split_group(s: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 1 => return Some((&s[1..i], &s[i + 1..])),
+            ')' => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+fn parse_params(s: &str) -> Result<Vec<Param>, AbiError> {
+    split_list(s)?
+        .into_iter()
+        .map(|kind| {
+            Ok(Param {
+                name: String::new(),
+                kind: ParamType::parse(kind)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let function = Function::parse("add_x(uint256)").unwrap();
+        assert_eq!(function.name, "add_x");
+        assert_eq!(function.inputs[0].kind, ParamType::Uint(256));
+        assert!(function.outputs.is_empty());
+        assert_eq!(hex::encode(function.selector()), "980cd0fc");
+        let function =
+            Function::parse("get(uint, (bool,bytes))(string)").unwrap();
+        assert_eq!(function.signature(), "get(uint256,(bool,bytes))");
+        assert_eq!(function.outputs[0].kind, ParamType::String);
+        for s in ["setX", "(uint256)", "f(uint256", "f()()()", "f g()", "f(x)"]
+        {
+            assert!(Function::parse(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_encode_input() {
+        let function = Function::parse("add_x(uint256)").unwrap();
+        let data = function.encode_input(&[42.into()]).unwrap();
+        assert_eq!(
+            hex::encode(&data),
+            concat!(
+                "980cd0fc",
+                "000000000000000000000000000000000000000000000000000000000000002a"
+            )
+        );
+        assert_eq!(
+            function.decode_input(&data),
+            Ok(vec![Token::Uint(42.into())])
+        );
+        assert_eq!(
+            function.decode_input(&data[1..]),
+            Err(AbiError::InvalidData)
+        );
+        let res = function.encode_input(&[]);
+        assert_eq!(res, Err(AbiError::ArgumentMismatch));
+        let res = function.encode_input(&["42".into()]);
+        assert_eq!(res, Err(AbiError::ArgumentMismatch));
+        let function = Function::parse("f()(int8[])").unwrap();
+        let data = encode(&[Token::Array(vec![Token::from(-1)])]);
+        assert_eq!(
+            function.decode_output(&data),
+            Ok(vec![Token::Array(vec![Token::from(-1)])])
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use crate::abi::{
+    AbiError, DecodedLog, Event, EventParam, Function, Param, ParamType,
+};
+use crate::types::Log;
+use serde::Deserialize;
+use std::path::Path;
+
+/// The interface of a contract, as produced by the Solidity compiler.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Abi {
+    pub functions: Vec<Function>,
+    pub events: Vec<Event>,
+    /// Custom errors, which are encoded like calls to functions of the same
+    /// name and inputs.
+    pub errors: Vec<Function>,
+}
+
+/// An item of a JSON ABI, see the Solidity ABI specification.
+#[derive(Deserialize)]
+struct JsonItem {
+    #[serde(rename = "type", default = "default_item_type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    inputs: Vec<JsonParam>,
+    #[serde(default)]
+    outputs: Vec<JsonParam>,
+    #[serde(default)]
+    anonymous: bool,
+}
+
+#[derive(Deserialize)]
+struct JsonParam {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    components: Vec<JsonParam>,
+    #[serde(default)]
+    indexed: bool,
+}
+
+fn default_item_type() -> String {
+    "function".to_string()
+}
+
+impl Abi {
+    /// Parses a JSON ABI. Constructors, fallback and receive functions are
+    /// skipped.
+    pub fn from_json(json: &str) -> Result<Self, AbiError> {
+        let items: Vec<JsonItem> = serde_json::from_str(json)
+            .map_err(|err| AbiError::InvalidJson(err.to_string()))?;
+        let mut abi = Abi::default();
+        for item in items {
+            match item.kind.as_str() {
+                "function" => abi.functions.push(Function {
+                    name: item.name,
+                    inputs: params(&item.inputs)?,
+                    outputs: params(&item.outputs)?,
+                }),
+                "event" => abi.events.push(Event {
+                    name: item.name,
+                    inputs: event_params(&item.inputs)?,
+                    anonymous: item.anonymous,
+                }),
+                "error" => abi.errors.push(Function {
+                    name: item.name,
+                    inputs: params(&item.inputs)?,
+                    outputs: Vec::new(),
+                }),
+                _ => (),
+            }
+        }
+        Ok(abi)
+    }
+
+    /// Reads and parses the JSON ABI in the given file.
+    pub fn load(path: &Path) -> Result<Self, AbiError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|err| AbiError::InvalidJson(err.to_string()))?;
+        Abi::from_json(&json)
+    }
+
+    /// Returns the function with the given name, or with the given signature
+    /// to pick one among overloads.
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        if name.contains('(') {
+            self.functions.iter().find(|f| f.signature() == name)
+        } else {
+            self.functions.iter().find(|f| f.name == name)
+        }
+    }
+
+    /// Returns the event with the given name, or with the given signature to
+    /// pick one among overloads.
+    pub fn event(&self, name: &str) -> Option<&Event> {
+        if name.contains('(') {
+            self.events.iter().find(|e| e.signature() == name)
+        } else {
+            self.events.iter().find(|e| e.name == name)
+        }
+    }
+
+    /// Decodes a log against the event whose signature matches its first
+    /// topic, or else against the first anonymous event it decodes with.
+    pub fn decode_log(&self, log: &Log) -> Result<DecodedLog, AbiError> {
+        let topic0 = log.topics.first();
+        let named = self
+            .events
+            .iter()
+            .find(|e| !e.anonymous && Some(&e.topic()) == topic0);
+        if let Some(event) = named {
+            return event.decode_log(log);
+        }
+        self.events
+            .iter()
+            .filter(|e| e.anonymous)
+            .find_map(|e| e.decode_log(log).ok())
+            .ok_or(AbiError::UnknownEvent)
+    }
+}
+
+fn event_params(params: &[JsonParam]) -> Result<Vec<EventParam>, AbiError> {
+    params
+        .iter()
+        .map(|param| {
+            Ok(EventParam {
+                name: param.name.clone(),
+                kind: param_type(param)?,
+                indexed: param.indexed,
+            })
+        })
+        .collect()
+}
+
+fn params(params: &[JsonParam]) -> Result<Vec<Param>, AbiError> {
+    params
+        .iter()
+        .map(|param| {
+            Ok(Param {
+                name: param.name.clone(),
+                kind: param_type(param)?,
+            })
+        })
+        .collect()
+}
+
+/// Returns the type of the parameter, where "tuple" stands for the types of
+/// its components, e.g. "tuple[]" with (uint256,bool) is "(uint256,bool)[]".
+fn param_type(param: &JsonParam) -> Result<ParamType, AbiError> {
+    match param.kind.strip_prefix("tuple") {
+        Some(suffix) => {
+            let components = param
+                .components
+                .iter()
+                .map(|c| Ok(param_type(c)?.to_string()))
+                .collect::<Result<Vec<_>, AbiError>>()?;
+            ParamType::parse(&format!("({}){}", components.join(","), suffix))
+        }
+        None => ParamType::parse(&param.kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::{encode, Token};
+    use ethereum_types::H256;
+
+    const JSON: &str = r#"[
+        {"type": "constructor", "inputs": []},
+        {
+            "type": "function",
+            "name": "setX",
+            "inputs": [{"name": "x", "type": "uint256"}],
+            "outputs": [],
+            "stateMutability": "nonpayable"
+        },
+        {
+            "type": "function",
+            "name": "orders",
+            "inputs": [{"name": "id", "type": "uint256"}],
+            "outputs": [{
+                "name": "",
+                "type": "tuple[]",
+                "components": [
+                    {"name": "owner", "type": "address"},
+                    {"name": "amounts", "type": "uint128[2]"}
+                ]
+            }],
+            "stateMutability": "view"
+        },
+        {"type": "function", "name": "orders", "inputs": [], "outputs": []},
+        {
+            "type": "event",
+            "name": "Add",
+            "inputs": [
+                {"name": "x", "type": "uint256", "indexed": true},
+                {"name": "doubled", "type": "uint256", "indexed": false}
+            ],
+            "anonymous": false
+        },
+        {
+            "type": "event",
+            "name": "Note",
+            "inputs": [{"name": "memo", "type": "string", "indexed": false}],
+            "anonymous": true
+        }
+    ]"#;
+
+    #[test]
+    fn test_from_json() {
+        let abi = Abi::from_json(JSON).unwrap();
+        assert_eq!(abi.functions.len(), 3);
+        let set_x = abi.function("setX").unwrap();
+        assert_eq!(set_x.signature(), "setX(uint256)");
+        assert_eq!(set_x.inputs[0].name, "x");
+        let orders = abi.function("orders(uint256)").unwrap();
+        assert_eq!(
+            orders.outputs[0].kind.to_string(),
+            "(address,uint128[2])[]"
+        );
+        assert!(abi.function("orders()").unwrap().inputs.is_empty());
+        assert!(abi.function("getX").is_none());
+        assert!(Abi::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn test_decode_log() {
+        let abi = Abi::from_json(JSON).unwrap();
+        let add = abi.event("Add(uint256,uint256)").unwrap();
+        assert!(add.inputs[0].indexed && !add.inputs[1].indexed);
+        let log = Log {
+            topics: vec![add.topic(), H256::from_low_u64_be(42)],
+            data: encode(&[Token::from(84)]),
+        };
+        let decoded = abi.decode_log(&log).unwrap();
+        assert_eq!(decoded.name, "Add");
+        assert_eq!(
+            decoded.params[1],
+            ("doubled".to_string(), Token::Uint(84.into()))
+        );
+        let log = Log {
+            topics: vec![],
+            data: encode(&["hi".into()]),
+        };
+        assert_eq!(abi.decode_log(&log).unwrap().name, "Note");
+        let log = Log {
+            topics: vec![H256::zero()],
+            data: vec![],
+        };
+        assert_eq!(abi.decode_log(&log), Err(AbiError::UnknownEvent));
+    }
+}
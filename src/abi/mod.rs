@@ -0,0 +1,56 @@
+mod decode;
+mod encode;
+mod event;
+mod function;
+mod json;
+mod param;
+mod revert;
+mod token;
+
+pub use decode::decode;
+pub use encode::encode;
+pub use event::{DecodedLog, Event, EventParam};
+pub use function::Function;
+pub use json::Abi;
+pub use param::{Param, ParamType};
+pub use revert::{panic_reason, Revert, ERROR_SELECTOR, PANIC_SELECTOR};
+pub use token::Token;
+
+use std::fmt;
+
+/// Error returned when parsing an ABI or encoding and decoding values.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AbiError {
+    /// A type or function signature could not be parsed.
+    InvalidSignature(String),
+    /// A JSON ABI could not be parsed.
+    InvalidJson(String),
+    /// The given values do not match the types to encode.
+    ArgumentMismatch,
+    /// The data is too short or malformed for the types to decode.
+    InvalidData,
+    /// No event of the ABI matches the log.
+    UnknownEvent,
+}
+
+impl fmt::Display for AbiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AbiError::InvalidSignature(sig) => {
+                write!(f, "invalid ABI signature: {}", sig)
+            }
+            AbiError::InvalidJson(msg) => {
+                write!(f, "invalid JSON ABI: {}", msg)
+            }
+            AbiError::ArgumentMismatch => {
+                write!(f, "arguments do not match the ABI types")
+            }
+            AbiError::InvalidData => {
+                write!(f, "data does not match the ABI types")
+            }
+            AbiError::UnknownEvent => write!(f, "log does not match any event"),
+        }
+    }
+}
+
+impl std::error::Error for AbiError {}
@@ -0,0 +1,205 @@
+use crate::abi::AbiError;
+use std::fmt;
+
+/// Type of a value in the Solidity ABI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParamType {
+    Address,
+    Bool,
+    /// Unsigned integer with the given number of bits.
+    Uint(usize),
+    /// Signed integer with the given number of bits.
+    Int(usize),
+    /// Byte array with the given length, from 1 to 32.
+    FixedBytes(usize),
+    Bytes,
+    String,
+    Array(Box<ParamType>),
+    FixedArray(Box<ParamType>, usize),
+    Tuple(Vec<ParamType>),
+}
+
+/// A named input or output of a function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Param {
+    pub name: String,
+    pub kind: ParamType,
+}
+
+impl ParamType {
+    /// Parses a type as written in a signature, e.g. "uint256[]" or
+    /// "(address,bytes)".
+    pub fn parse(s: &str) -> Result<Self, AbiError> {
+        let invalid = || AbiError::InvalidSignature(s.to_string());
+        let s = s.trim();
+        if let Some(inner) = s.strip_suffix(']') {
+            let open = inner.rfind('[').ok_or_else(invalid)?;
+            let elem = Box::new(ParamType::parse(&inner[..open])?);
+            let len = &inner[open + 1..];
+            if len.is_empty() {
+                return Ok(ParamType::Array(elem));
+            }
+            let len = len.parse().map_err(|_| invalid())?;
+            return Ok(ParamType::FixedArray(elem, len));
+        }
+        if let Some(inner) = s.strip_prefix('(') {
+            let inner = inner.strip_suffix(')').ok_or_else(invalid)?;
+            let types = split_list(inner)?
+                .into_iter()
+                .map(ParamType::parse)
+                .collect::<Result<_, _>>()?;
+            return Ok(ParamType::Tuple(types));
+        }
+        let kind = match s {
+            "address" => ParamType::Address,
+            "bool" => ParamType::Bool,
+            "bytes" => ParamType::Bytes,
+            "string" => ParamType::String,
+            "uint" => ParamType::Uint(256),
+            "int" => ParamType::Int(256),
+            _ => parse_sized(s).ok_or_else(invalid)?,
+        };
+        Ok(kind)
+    }
+
+    /// Returns whether values of the type are encoded out of place, after
+    /// the fixed-size head of the enclosing list.
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            ParamType::Bytes | ParamType::String | ParamType::Array(_) => true,
+            ParamType::FixedArray(elem, _) => elem.is_dynamic(),
+            ParamType::Tuple(types) => types.iter().any(|t| t.is_dynamic()),
+            _ => false,
+        }
+    }
+
+    /// Returns the size of the type in the head of the enclosing list.
+    pub fn head_size(&self) -> usize {
+        match self {
+            _ if self.is_dynamic() => 32,
+            ParamType::FixedArray(elem, len) => elem.head_size() * len,
+            ParamType::Tuple(types) => {
+                types.iter().map(|t| t.head_size()).sum()
+            }
+            _ => 32,
+        }
+    }
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParamType::Address => write!(f, "address"),
+            ParamType::Bool => write!(f, "bool"),
+            ParamType::Uint(bits) => write!(f, "uint{}", bits),
+            ParamType::Int(bits) => write!(f, "int{}", bits),
+            ParamType::FixedBytes(len) => write!(f, "bytes{}", len),
+            ParamType::Bytes => write!(f, "bytes"),
+            ParamType::String => write!(f, "string"),
+            ParamType::Array(elem) => write!(f, "{}[]", elem),
+            ParamType::FixedArray(elem, len) => write!(f, "{}[{}]", elem, len),
+            ParamType::Tuple(types) => write!(f, "({})", join(types)),
+        }
+    }
+}
+
+/// Parses the integer and fixed-size byte array types, e.g. "uint8".
+fn parse_sized(s: &str) -> Option<ParamType> {
+    if let Some(bits) = s.strip_prefix("uint") {
+        let bits = bits.parse().ok().filter(valid_bits)?;
+        Some(ParamType::Uint(bits))
+    } else if let Some(bits) = s.strip_prefix("int") {
+        let bits = bits.parse().ok().filter(valid_bits)?;
+        Some(ParamType::Int(bits))
+    } else {
+        let len = s.strip_prefix("bytes")?.parse().ok()?;
+        (1..=32)
+            .contains(&len)
+            .then_some(ParamType::FixedBytes(len))
+    }
+}
+
+fn valid_bits(bits: &usize) -> bool {
+    bits.is_multiple_of(8) && (8..=256).contains(bits)
+}
+
+/// Returns the types separated by commas, as in a signature.
+pub fn join(types: &[ParamType]) -> String {
+    let types: Vec<_> = types.iter().map(|t| t.to_string()).collect();
+    types.join(",")
+}
+
+/// Splits a comma-separated list of types at the top level of nesting.
+pub fn split_list(s: &str) -> Result<Vec<&str>, AbiError> {
+    if s.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| AbiError::InvalidSignature(s.to_string()))?
+            }
+            ',' if depth == 0 => {
+                items.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    if depth != 0 {
+        return Err(AbiError::InvalidSignature(s.to_string()));
+    }
+    items.push(&s[start..]);
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let parse = |s| ParamType::parse(s).unwrap();
+        assert_eq!(parse("uint256"), ParamType::Uint(256));
+        assert_eq!(parse("uint"), ParamType::Uint(256));
+        assert_eq!(parse("int8"), ParamType::Int(8));
+        assert_eq!(parse("bytes32"), ParamType::FixedBytes(32));
+        assert_eq!(
+            parse("uint8[2][]"),
+            ParamType::Array(Box::new(ParamType::FixedArray(
+                Box::new(ParamType::Uint(8)),
+                2
+            )))
+        );
+        assert_eq!(
+            parse("(address,(bool,string))"),
+            ParamType::Tuple(vec![
+                ParamType::Address,
+                ParamType::Tuple(vec![ParamType::Bool, ParamType::String])
+            ])
+        );
+        for s in [
+            "uint7", "uint264", "bytes33", "bytes0", "foo", "(bool", "[]",
+        ] {
+            assert!(ParamType::parse(s).is_err(), "{}", s);
+        }
+        let s = "(uint8[2],bytes)[]";
+        assert_eq!(parse(s).to_string(), s);
+    }
+
+    #[test]
+    fn test_head_size() {
+        let parse = |s| ParamType::parse(s).unwrap();
+        assert_eq!(parse("uint256[3]").head_size(), 96);
+        assert_eq!(parse("(bool,uint8[2])").head_size(), 96);
+        assert_eq!(parse("(bool,bytes)").head_size(), 32);
+        assert!(parse("string[2]").is_dynamic());
+        assert!(!parse("(bool,address)[2]").is_dynamic());
+    }
+}
@@ -0,0 +1,177 @@
+use crate::abi::{decode, Abi, ParamType, Token};
+use ethereum_types::U256;
+use std::fmt;
+
+/// Selector of Error(string), the revert data of require and revert with a
+/// message.
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Selector of Panic(uint256), the revert data of failed assertions and
+/// checked arithmetic.
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// The reason of a revert, decoded from its data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Revert {
+    /// Error(string) with the given message.
+    Error(String),
+    /// Panic(uint256) with the given code, see panic_reason.
+    Panic(U256),
+    /// A custom error of an ABI with its arguments.
+    Custom {
+        name: String,
+        params: Vec<(String, Token)>,
+    },
+    /// Data that does not match any known error.
+    Raw(Vec<u8>),
+}
+
+impl Revert {
+    /// Decodes the revert data as Error(string) or Panic(uint256).
+    pub fn decode(data: &[u8]) -> Self {
+        let builtin = |selector, kind| {
+            let mut tokens =
+                decode(&[kind], data.strip_prefix(selector)?).ok()?;
+            tokens.pop()
+        };
+        if let Some(Token::String(msg)) =
+            builtin(&ERROR_SELECTOR, ParamType::String)
+        {
+            return Revert::Error(msg);
+        }
+        if let Some(Token::Uint(code)) =
+            builtin(&PANIC_SELECTOR, ParamType::Uint(256))
+        {
+            return Revert::Panic(code);
+        }
+        Revert::Raw(data.to_vec())
+    }
+}
+
+/// Returns the meaning of a panic code emitted by the Solidity compiler.
+pub fn panic_reason(code: U256) -> Option<&'static str> {
+    if code > U256::from(u8::MAX) {
+        return None;
+    }
+    let reason = match code.low_u64() {
+        0x00 => "generic compiler panic",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic overflow or underflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "invalid enum value",
+        0x22 => "invalid storage byte array encoding",
+        0x31 => "pop on empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "call to uninitialized function",
+        _ => return None,
+    };
+    Some(reason)
+}
+
+impl Abi {
+    /// Decodes the revert data as Error(string), Panic(uint256), or one of
+    /// the custom errors of the ABI.
+    pub fn decode_revert(&self, data: &[u8]) -> Revert {
+        let revert = Revert::decode(data);
+        if !matches!(revert, Revert::Raw(_)) {
+            return revert;
+        }
+        for error in &self.errors {
+            if let Ok(values) = error.decode_input(data) {
+                let names = error.inputs.iter().map(|p| p.name.clone());
+                return Revert::Custom {
+                    name: error.name.clone(),
+                    params: names.zip(values).collect(),
+                };
+            }
+        }
+        revert
+    }
+}
+
+impl fmt::Display for Revert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Revert::Error(msg) => write!(f, "{}", msg),
+            Revert::Panic(code) => match panic_reason(*code) {
+                Some(reason) => {
+                    write!(f, "panic 0x{:02x}: {}", code.low_u64(), reason)
+                }
+                None => write!(f, "panic 0x{:x}", code),
+            },
+            Revert::Custom { name, params } => {
+                let values: Vec<_> =
+                    params.iter().map(|(_, value)| value.to_string()).collect();
+                write!(f, "{}({})", name, values.join(", "))
+            }
+            Revert::Raw(data) if data.is_empty() => write!(f, "no data"),
+            Revert::Raw(data) => write!(f, "0x{}", hex::encode(data)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::encode;
+
+    fn with_selector(selector: &[u8], tokens: &[Token]) -> Vec<u8> {
+        let mut data = selector.to_vec();
+        data.extend(encode(tokens));
+        data
+    }
+
+    #[test]
+    fn test_decode() {
+        let data = with_selector(&ERROR_SELECTOR, &["Not owner".into()]);
+        assert_eq!(Revert::decode(&data), Revert::Error("Not owner".into()));
+        assert_eq!(Revert::decode(&data).to_string(), "Not owner");
+        let data = with_selector(&PANIC_SELECTOR, &[0x11.into()]);
+        assert_eq!(Revert::decode(&data), Revert::Panic(0x11.into()));
+        assert_eq!(
+            Revert::decode(&data).to_string(),
+            "panic 0x11: arithmetic overflow or underflow"
+        );
+        let data = with_selector(&PANIC_SELECTOR, &[0x99.into()]);
+        assert_eq!(Revert::decode(&data).to_string(), "panic 0x99");
+        // Error(string) whose argument is not a valid string.
+        let data = with_selector(&ERROR_SELECTOR, &[1.into()]);
+        assert_eq!(Revert::decode(&data), Revert::Raw(data.clone()));
+        assert_eq!(Revert::decode(&[]).to_string(), "no data");
+        assert_eq!(Revert::decode(&[0xab]).to_string(), "0xab");
+    }
+
+    #[test]
+    fn test_decode_custom() {
+        let abi = Abi::from_json(
+            r#"[{
+                "type": "error",
+                "name": "InsufficientBalance",
+                "inputs": [
+                    {"name": "available", "type": "uint256"},
+                    {"name": "required", "type": "uint256"}
+                ]
+            }]"#,
+        )
+        .unwrap();
+        let error = &abi.errors[0];
+        let data = with_selector(&error.selector(), &[100.into(), 200.into()]);
+        let revert = abi.decode_revert(&data);
+        assert_eq!(
+            revert,
+            Revert::Custom {
+                name: "InsufficientBalance".into(),
+                params: vec![
+                    ("available".into(), Token::Uint(100.into())),
+                    ("required".into(), Token::Uint(200.into())),
+                ],
+            }
+        );
+        assert_eq!(revert.to_string(), "InsufficientBalance(100, 200)");
+        let data = with_selector(&ERROR_SELECTOR, &["no".into()]);
+        assert_eq!(abi.decode_revert(&data), Revert::Error("no".into()));
+        let data = with_selector(&error.selector(), &[100.into()]);
+        assert_eq!(abi.decode_revert(&data), Revert::Raw(data.clone()));
+    }
+}
@@ -0,0 +1,258 @@
+use crate::abi::ParamType;
+use ethereum_types::{Address, H256, U256};
+use std::fmt;
+
+/// A value in the Solidity ABI. Signed integers are held in two's complement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token {
+    Address(Address),
+    Bool(bool),
+    Uint(U256),
+    Int(U256),
+    FixedBytes(Vec<u8>),
+    Bytes(Vec<u8>),
+    String(String),
+    Array(Vec<Token>),
+    FixedArray(Vec<Token>),
+    Tuple(Vec<Token>),
+}
+
+impl Token {
+    /// Returns whether the value can be encoded as the given type.
+    pub fn matches(&self, kind: &ParamType) -> bool {
+        match (self, kind) {
+            (Token::Address(_), ParamType::Address) => true,
+            (Token::Bool(_), ParamType::Bool) => true,
+            // Integers convert between signed and unsigned types when they
+            // are in range, so that literals can be passed to either.
+            (Token::Uint(value), ParamType::Uint(bits)) => {
+                value.bits() <= *bits
+            }
+            (Token::Uint(value), ParamType::Int(bits)) => value.bits() < *bits,
+            (Token::Int(value), ParamType::Uint(bits)) => {
+                !value.bit(255) && value.bits() <= *bits
+            }
+            (Token::Int(value), ParamType::Int(bits)) => {
+                fits_signed(*value, *bits)
+            }
+            (Token::FixedBytes(data), ParamType::FixedBytes(len)) => {
+                data.len() == *len
+            }
+            (Token::Bytes(_), ParamType::Bytes) => true,
+            (Token::String(_), ParamType::String) => true,
+            (Token::Array(items), ParamType::Array(elem)) => {
+                items.iter().all(|item| item.matches(elem))
+            }
+            (Token::FixedArray(items), ParamType::FixedArray(elem, len)) => {
+                items.len() == *len
+                    && items.iter().all(|item| item.matches(elem))
+            }
+            (Token::Tuple(items), ParamType::Tuple(types)) => {
+                items.len() == types.len()
+                    && items.iter().zip(types).all(|(item, t)| item.matches(t))
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether the value is encoded out of place, see
+    /// ParamType::is_dynamic.
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            Token::Bytes(_) | Token::String(_) | Token::Array(_) => true,
+            Token::FixedArray(items) | Token::Tuple(items) => {
+                items.iter().any(|item| item.is_dynamic())
+            }
+            _ => false,
+        }
+    }
+
+    pub fn into_address(self) -> Option<Address> {
+        match self {
+            Token::Address(address) => Some(address),
+            _ => None,
+        }
+    }
+
+    pub fn into_bool(self) -> Option<bool> {
+        match self {
+            Token::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of an unsigned or signed integer, the latter in
+    /// two's complement.
+    pub fn into_uint(self) -> Option<U256> {
+        match self {
+            Token::Uint(value) | Token::Int(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the content of a fixed or dynamic byte array.
+    pub fn into_bytes(self) -> Option<Vec<u8>> {
+        match self {
+            Token::FixedBytes(data) | Token::Bytes(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn into_string(self) -> Option<String> {
+        match self {
+            Token::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the items of an array or a tuple.
+    pub fn into_items(self) -> Option<Vec<Token>> {
+        match self {
+            Token::Array(items)
+            | Token::FixedArray(items)
+            | Token::Tuple(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// Returns whether the two's complement value is within the range of a
+/// signed integer of the given number of bits.
+fn fits_signed(value: U256, bits: usize) -> bool {
+    if bits >= 256 {
+        return true;
+    }
+    // All the bits above the sign bit must be copies of it.
+    let high = value >> (bits - 1);
+    high.is_zero() || high == U256::MAX >> (bits - 1)
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter, items: &[Token]| {
+            let items: Vec<_> = items.iter().map(|t| t.to_string()).collect();
+            write!(f, "{}", items.join(", "))
+        };
+        match self {
+            Token::Address(address) => write!(f, "{:?}", address),
+            Token::Bool(value) => write!(f, "{}", value),
+            Token::Uint(value) => write!(f, "{}", value),
+            Token::Int(value) if value.bit(255) => {
+                write!(f, "-{}", (!*value).overflowing_add(1.into()).0)
+            }
+            Token::Int(value) => write!(f, "{}", value),
+            Token::FixedBytes(data) | Token::Bytes(data) => {
+                write!(f, "0x{}", hex::encode(data))
+            }
+            Token::String(value) => write!(f, "{:?}", value),
+            Token::Array(items) | Token::FixedArray(items) => {
+                write!(f, "[")?;
+                list(f, items)?;
+                write!(f, "]")
+            }
+            Token::Tuple(items) => {
+                write!(f, "(")?;
+                list(f, items)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl From<Address> for Token {
+    fn from(value: Address) -> Self {
+        Token::Address(value)
+    }
+}
+
+impl From<bool> for Token {
+    fn from(value: bool) -> Self {
+        Token::Bool(value)
+    }
+}
+
+impl From<u64> for Token {
+    fn from(value: u64) -> Self {
+        Token::Uint(value.into())
+    }
+}
+
+impl From<U256> for Token {
+    fn from(value: U256) -> Self {
+        Token::Uint(value)
+    }
+}
+
+impl From<i64> for Token {
+    fn from(value: i64) -> Self {
+        let value = if value < 0 {
+            !U256::from(value.unsigned_abs() - 1)
+        } else {
+            U256::from(value)
+        };
+        Token::Int(value)
+    }
+}
+
+impl From<i32> for Token {
+    fn from(value: i32) -> Self {
+        Token::from(value as i64)
+    }
+}
+
+impl From<H256> for Token {
+    fn from(value: H256) -> Self {
+        Token::FixedBytes(value.as_bytes().to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Token {
+    fn from(value: Vec<u8>) -> Self {
+        Token::Bytes(value)
+    }
+}
+
+impl From<&str> for Token {
+    fn from(value: &str) -> Self {
+        Token::String(value.to_string())
+    }
+}
+
+impl From<String> for Token {
+    fn from(value: String) -> Self {
+        Token::String(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let parse = |s| ParamType::parse(s).unwrap();
+        assert!(Token::from(255).matches(&parse("uint8")));
+        assert!(!Token::from(256).matches(&parse("uint8")));
+        assert!(Token::from(-128).matches(&parse("int8")));
+        assert!(!Token::from(-129).matches(&parse("int8")));
+        assert!(Token::from(127u64).matches(&parse("int8")));
+        assert!(!Token::from(128u64).matches(&parse("int8")));
+        assert!(!Token::from(-1).matches(&parse("uint256")));
+        let token =
+            Token::Array(vec![Token::Tuple(vec![true.into(), "a".into()])]);
+        assert!(token.matches(&parse("(bool,string)[]")));
+        assert!(!token.matches(&parse("(bool,string)[2]")));
+        assert!(!token.matches(&parse("(bool,bytes)[]")));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Token::from(-5).to_string(), "-5");
+        let token = Token::Tuple(vec![
+            42.into(),
+            Token::Array(vec!["a".into()]),
+            Token::Bytes(vec![0xab]),
+        ]);
+        assert_eq!(token.to_string(), "(42, [\"a\"], 0xab)");
+    }
+}
@@ -0,0 +1,109 @@
+use ethereum_types::H256;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+const JUMPDEST: u8 = 0x5b;
+const PUSH1: u8 = 0x60;
+const PUSH32: u8 = 0x7f;
+
+/// Number of jump tables kept before the cache is emptied.
+const MAX_CACHED: usize = 1024;
+
+/// A bitmap of the code positions that are valid jump destinations.
+#[derive(Debug, PartialEq)]
+pub struct JumpTable(Vec<u64>);
+
+impl JumpTable {
+    /// Scans the code once and marks every JUMPDEST outside of PUSH data.
+    pub fn analyze(code: &[u8]) -> Self {
+        let mut bits = vec![0u64; code.len().div_ceil(64)];
+        let mut pc = 0;
+        while pc < code.len() {
+            match code[pc] {
+                JUMPDEST => bits[pc / 64] |= 1 << (pc % 64),
+                op @ PUSH1..=PUSH32 => pc += (op - PUSH1 + 1) as usize,
+                _ => (),
+            }
+            pc += 1;
+        }
+        Self(bits)
+    }
+
+    /// Returns whether the given position is a valid jump destination.
+    pub fn is_valid(&self, pc: usize) -> bool {
+        match self.0.get(pc / 64) {
+            Some(bits) => bits & (1 << (pc % 64)) != 0,
+            None => false,
+        }
+    }
+}
+
+/// Jump tables of previously analyzed code, keyed by the code hash.
+#[derive(Default)]
+pub struct JumpCache(HashMap<H256, Rc<JumpTable>>);
+
+impl JumpCache {
+    /// Returns the jump table of the code with the given hash, analyzing it
+    /// on first use.
+    pub fn get(&mut self, hash: H256, code: &[u8]) -> Rc<JumpTable> {
+        if let Some(table) = self.0.get(&hash) {
+            return table.clone();
+        }
+        if self.0.len() >= MAX_CACHED {
+            self.0.clear();
+        }
+        let table = Rc::new(JumpTable::analyze(code));
+        self.0.insert(hash, table.clone());
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze() {
+        // JUMPDEST, PUSH1 0x5b, JUMPDEST, PUSH2 0x5b5b, STOP, JUMPDEST
+        let table =
+            JumpTable::analyze(&hex::decode("5b605b5b615b5b005b").unwrap());
+        assert!(table.is_valid(0));
+        assert!(!table.is_valid(1));
+        assert!(!table.is_valid(2));
+        assert!(table.is_valid(3));
+        assert!(!table.is_valid(5));
+        assert!(!table.is_valid(6));
+        assert!(!table.is_valid(7));
+        assert!(table.is_valid(8));
+        assert!(!table.is_valid(9));
+        assert!(!table.is_valid(usize::MAX));
+    }
+
+    #[test]
+    fn test_analyze_truncated_push() {
+        // PUSH32 with only three bytes of data left.
+        let table = JumpTable::analyze(&hex::decode("7f5b5b5b").unwrap());
+        assert!((0..4).all(|pc| !table.is_valid(pc)));
+        let mut code = vec![0x00; 64];
+        code.push(0x5b);
+        let table = JumpTable::analyze(&code);
+        assert!(table.is_valid(64));
+        assert!(!table.is_valid(63));
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut cache = JumpCache::default();
+        let hash = H256::repeat_byte;
+        let first = cache.get(hash(1), &[0x5b, 0x00]);
+        let second = cache.get(hash(1), &[0x5b, 0x00]);
+        let other = cache.get(hash(2), &[0x00, 0x5b]);
+        assert!(Rc::ptr_eq(&first, &second));
+        assert!(!Rc::ptr_eq(&first, &other));
+        assert!(other.is_valid(1));
+        for n in 0..MAX_CACHED as u64 + 10 {
+            cache.get(H256::from_low_u64_be(n), &[]);
+        }
+        assert!(cache.0.len() <= MAX_CACHED);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::db::{DbError, WriteOp};
+use crate::types::{code_hash, Account};
+use ethereum_types::{Address, H256, U256};
+
+// Key layout shared by the on-disk databases. Keys of the same kind are
+// grouped by a one byte prefix so that they can be scanned in order.
+pub const ACCOUNT_PREFIX: u8 = b'a';
+pub const STORAGE_PREFIX: u8 = b's';
+pub const BLOCK_HASH_PREFIX: u8 = b'h';
+pub const CODE_PREFIX: u8 = b'c';
+
+/// Returns the key of the account at the given address.
+pub fn account_key(address: Address) -> Vec<u8> {
+    let mut key = vec![ACCOUNT_PREFIX];
+    key.extend_from_slice(address.as_bytes());
+    key
+}
+
+/// Returns the common prefix of all the storage keys of the given address.
+pub fn storage_prefix(address: Address) -> Vec<u8> {
+    let mut key = vec![STORAGE_PREFIX];
+    key.extend_from_slice(address.as_bytes());
+    key
+}
+
+/// Returns the key of the storage slot of the given address.
+pub fn storage_key(address: Address, slot: U256) -> Vec<u8> {
+    let mut key = storage_prefix(address);
+    key.extend_from_slice(&u256_to_bytes(slot));
+    key
+}
+
+/// Returns the key of the hash of the block with the given number.
+pub fn block_hash_key(number: u64) -> Vec<u8> {
+    let mut key = vec![BLOCK_HASH_PREFIX];
+    key.extend_from_slice(&number.to_be_bytes());
+    key
+}
+
+/// Returns the key of the code with the given hash.
+pub fn code_key(hash: H256) -> Vec<u8> {
+    let mut key = vec![CODE_PREFIX];
+    key.extend_from_slice(hash.as_bytes());
+    key
+}
+
+pub fn u256_to_bytes(value: U256) -> [u8; 32] {
+    let mut slice = [0u8; 32];
+    value.to_big_endian(&mut slice);
+    slice
+}
+
+/// Encodes the account as nonce (8 bytes) ++ balance (32 bytes) ++ code
+/// hash (32 bytes).
+pub fn encode_account(account: &Account) -> Vec<u8> {
+    let mut data = account.nonce.to_be_bytes().to_vec();
+    data.extend_from_slice(&u256_to_bytes(account.balance));
+    data.extend_from_slice(account.code_hash.as_bytes());
+    data
+}
+
+pub fn decode_account(data: &[u8]) -> Result<Account, DbError> {
+    if data.len() != 72 {
+        return Err(DbError::Corrupted);
+    }
+    let mut nonce = [0u8; 8];
+    nonce.copy_from_slice(&data[..8]);
+    Ok(Account {
+        nonce: u64::from_be_bytes(nonce),
+        balance: U256::from_big_endian(&data[8..40]),
+        code_hash: H256::from_slice(&data[40..]),
+    })
+}
+
+pub fn decode_word(data: &[u8]) -> Result<U256, DbError> {
+    if data.len() > 32 {
+        return Err(DbError::Corrupted);
+    }
+    Ok(U256::from_big_endian(data))
+}
+
+pub fn decode_hash(data: &[u8]) -> Result<H256, DbError> {
+    if data.len() != 32 {
+        return Err(DbError::Corrupted);
+    }
+    Ok(H256::from_slice(data))
+}
+
+/// Decodes the address from an account key without its prefix.
+pub fn decode_address(key: &[u8]) -> Result<Address, DbError> {
+    if key.len() != 20 {
+        return Err(DbError::Corrupted);
+    }
+    Ok(Address::from_slice(key))
+}
+
+/// Decodes the slot from a storage key without its prefix.
+pub fn decode_slot(key: &[u8]) -> Result<U256, DbError> {
+    if key.len() != 32 {
+        return Err(DbError::Corrupted);
+    }
+    Ok(U256::from_big_endian(key))
+}
+
+/// Returns the key of the change and its encoded value, or None if the key
+/// should be deleted.
+pub fn encode_op(op: &WriteOp) -> (Vec<u8>, Option<Vec<u8>>) {
+    match op {
+        WriteOp::Account(address, account) => {
+            let key = account_key(*address);
+            if account.is_empty() {
+                (key, None)
+            } else {
+                (key, Some(encode_account(account)))
+            }
+        }
+        WriteOp::Storage(address, key, value) => {
+            let key = storage_key(*address, *key);
+            if value.is_zero() {
+                (key, None)
+            } else {
+                (key, Some(u256_to_bytes(*value).to_vec()))
+            }
+        }
+        WriteOp::Code(code) => (code_key(code_hash(code)), Some(code.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_encoding() {
+        let account = Account {
+            nonce: 7,
+            balance: 100.into(),
+            code_hash: code_hash(&[0x60, 0x00]),
+        };
+        let data = encode_account(&account);
+        assert_eq!(data.len(), 72);
+        assert_eq!(decode_account(&data), Ok(account));
+        assert_eq!(decode_account(&data[..71]), Err(DbError::Corrupted));
+    }
+
+    #[test]
+    fn test_storage_keys_sorted() {
+        let addr = Address::from_low_u64_be(1);
+        let key1 = storage_key(addr, 0xff.into());
+        let key2 = storage_key(addr, 0x100.into());
+        assert!(key1 < key2);
+        assert!(key1.starts_with(&storage_prefix(addr)));
+        assert_eq!(decode_slot(&key2[21..]), Ok(0x100.into()));
+    }
+}
@@ -1,6 +1,11 @@
-use crate::db::Database;
-use ethereum_types::U256;
+use crate::db::codec;
+use crate::db::{BlockHashProvider, Database, DbError, DbIter, WriteOp};
+use crate::types::Account;
+use ethereum_types::{Address, H256, U256};
+use leveldb::batch::{Batch, Writebatch};
 use leveldb::database;
+use leveldb::error;
+use leveldb::iterator::{Iterable, LevelDBIterator};
 use leveldb::kv::KV;
 use leveldb::options::{Options, ReadOptions, WriteOptions};
 use std::path;
@@ -8,17 +13,22 @@ use std::path;
 extern crate db_key as key;
 use key::Key;
 
-struct MyKey(U256);
+/// LevelDB key wrapping the raw bytes laid out by the codec module.
+struct MyKey(Vec<u8>);
 
 impl Key for MyKey {
     fn from_u8(key: &[u8]) -> Self {
-        Self(U256::from_big_endian(key))
+        Self(key.to_vec())
     }
 
     fn as_slice<T, F: Fn(&[u8]) -> T>(&self, f: F) -> T {
-        let mut slice = [0u8; 32];
-        self.0.to_big_endian(&mut slice);
-        f(&slice)
+        f(&self.0)
+    }
+}
+
+impl From<error::Error> for DbError {
+    fn from(err: error::Error) -> Self {
+        DbError::Backend(err.to_string())
     }
 }
 
@@ -27,57 +37,281 @@ pub struct LevelDB {
 }
 
 impl LevelDB {
-    /// Creates a new LevelDB file backed database instance.
-    pub fn new(path: &path::Path) -> Self {
+    /// Opens or creates a LevelDB file backed database instance.
+    pub fn new(path: &path::Path) -> Result<Self, DbError> {
         let mut options = Options::new();
         options.create_if_missing = true;
-        Self {
-            db: database::Database::open(path, options).unwrap(),
+        Ok(Self {
+            db: database::Database::open(path, options)?,
+        })
+    }
+
+    /// Sets the hash of the block with the given number.
+    pub fn set_block_hash(
+        &mut self,
+        number: u64,
+        hash: H256,
+    ) -> Result<(), DbError> {
+        let key = MyKey(codec::block_hash_key(number));
+        Ok(self.db.put(WriteOptions::new(), &key, hash.as_bytes())?)
+    }
+
+    /// Returns the entries whose keys start with the given prefix, with the
+    /// prefix stripped from the keys. The iterator starts at the prefix, so
+    /// the keys before it are not read.
+    fn scan(&self, prefix: Vec<u8>) -> DbIter<'_, (Vec<u8>, Vec<u8>)> {
+        let len = prefix.len();
+        let iter = self.db.iter(ReadOptions::new());
+        iter.seek(&MyKey(prefix.clone()));
+        let iter = iter
+            .map(|(key, value)| (key.0, value))
+            .take_while(move |(key, _)| key.starts_with(&prefix))
+            .map(move |(key, value)| Ok((key[len..].to_vec(), value)));
+        Box::new(iter)
+    }
+
+    fn write(&mut self, op: WriteOp) -> Result<(), DbError> {
+        let wo = WriteOptions::new();
+        match codec::encode_op(&op) {
+            (key, None) => self.db.delete(wo, MyKey(key))?,
+            (key, Some(value)) => self.db.put(wo, MyKey(key), &value)?,
+        }
+        Ok(())
+    }
+}
+
+impl BlockHashProvider for LevelDB {
+    fn block_hash(&self, number: u64) -> Result<Option<H256>, DbError> {
+        let key = MyKey(codec::block_hash_key(number));
+        match self.db.get(ReadOptions::new(), &key)? {
+            None => Ok(None),
+            Some(v) => Ok(Some(codec::decode_hash(&v)?)),
         }
     }
 }
 
 impl Database for LevelDB {
-    fn get(&self, key: U256) -> U256 {
-        match self.db.get(ReadOptions::new(), &MyKey(key)).unwrap() {
-            None => 0.into(),
-            Some(v) => MyKey::from_u8(&v).0,
+    fn get_account(&self, address: Address) -> Result<Account, DbError> {
+        let key = MyKey(codec::account_key(address));
+        match self.db.get(ReadOptions::new(), &key)? {
+            None => Ok(Account::default()),
+            Some(v) => codec::decode_account(&v),
         }
     }
 
-    fn set(&mut self, key: U256, value: U256) {
-        let wo = WriteOptions::new();
-        if value == U256::default() {
-            self.db.delete(wo, &MyKey(key)).unwrap()
-        } else {
-            MyKey(value).as_slice(|v| self.db.put(wo, MyKey(key), v).unwrap())
+    fn set_account(
+        &mut self,
+        address: Address,
+        account: Account,
+    ) -> Result<(), DbError> {
+        self.write(WriteOp::Account(address, account))
+    }
+
+    fn get_code(&self, hash: H256) -> Result<Option<Vec<u8>>, DbError> {
+        let key = MyKey(codec::code_key(hash));
+        Ok(self.db.get(ReadOptions::new(), &key)?)
+    }
+
+    fn set_code(&mut self, code: Vec<u8>) -> Result<(), DbError> {
+        self.write(WriteOp::Code(code))
+    }
+
+    fn get(&self, address: Address, key: U256) -> Result<U256, DbError> {
+        let key = MyKey(codec::storage_key(address, key));
+        match self.db.get(ReadOptions::new(), &key)? {
+            None => Ok(0.into()),
+            Some(v) => codec::decode_word(&v),
         }
     }
+
+    fn set(
+        &mut self,
+        address: Address,
+        key: U256,
+        value: U256,
+    ) -> Result<(), DbError> {
+        self.write(WriteOp::Storage(address, key, value))
+    }
+
+    fn accounts(&self) -> DbIter<'_, Address> {
+        let iter = self
+            .scan(vec![codec::ACCOUNT_PREFIX])
+            .map(|entry| codec::decode_address(&entry?.0));
+        Box::new(iter)
+    }
+
+    fn storage(&self, address: Address) -> DbIter<'_, (U256, U256)> {
+        let iter = self.scan(codec::storage_prefix(address)).map(|entry| {
+            let (key, value) = entry?;
+            Ok((codec::decode_slot(&key)?, codec::decode_word(&value)?))
+        });
+        Box::new(iter)
+    }
+
+    fn write_batch(&mut self, batch: Vec<WriteOp>) -> Result<(), DbError> {
+        let mut wb = Writebatch::new();
+        for op in batch.iter() {
+            match codec::encode_op(op) {
+                (key, None) => wb.delete(MyKey(key)),
+                (key, Some(value)) => wb.put(MyKey(key), &value),
+            }
+        }
+        Ok(self.db.write(WriteOptions::new(), &wb)?)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::code_hash;
     use tempdir::TempDir;
 
     #[test]
     fn test_memory_database_empty() {
         let dir = TempDir::new("leveldbtest").unwrap();
-        let db = LevelDB::new(&dir.path());
-        assert_eq!(db.get(999.into()), 0.into());
+        let db = LevelDB::new(&dir.path()).unwrap();
+        assert_eq!(db.get(Address::zero(), 999.into()).unwrap(), 0.into());
+        assert_eq!(
+            db.get_account(Address::zero()).unwrap(),
+            Account::default()
+        );
     }
 
     #[test]
     fn test_memory_database_get_set() {
         let dir = TempDir::new("leveldbtest").unwrap();
-        let mut db = LevelDB::new(&dir.path());
-        db.set(123.into(), 456.into());
-        assert_eq!(db.get(123.into()), 456.into());
-        assert_eq!(db.get(124.into()), 0.into());
-        db.set(123.into(), 789.into());
-        assert_eq!(db.get(123.into()), 789.into());
-        assert_eq!(db.get(124.into()), 0.into());
-        db.set(123.into(), 0.into());
-        assert_eq!(db.get(123.into()), 0.into());
+        let mut db = LevelDB::new(&dir.path()).unwrap();
+        let addr = Address::zero();
+        db.set(addr, 123.into(), 456.into()).unwrap();
+        assert_eq!(db.get(addr, 123.into()).unwrap(), 456.into());
+        assert_eq!(db.get(addr, 124.into()).unwrap(), 0.into());
+        db.set(addr, 123.into(), 789.into()).unwrap();
+        assert_eq!(db.get(addr, 123.into()).unwrap(), 789.into());
+        assert_eq!(db.get(addr, 124.into()).unwrap(), 0.into());
+        db.set(addr, 123.into(), 0.into()).unwrap();
+        assert_eq!(db.get(addr, 123.into()).unwrap(), 0.into());
+    }
+
+    #[test]
+    fn test_memory_database_addresses() {
+        let dir = TempDir::new("leveldbtest").unwrap();
+        let mut db = LevelDB::new(&dir.path()).unwrap();
+        let addr1 = Address::from_low_u64_be(1);
+        let addr2 = Address::from_low_u64_be(2);
+        db.set(addr1, 123.into(), 456.into()).unwrap();
+        db.set(addr2, 123.into(), 789.into()).unwrap();
+        assert_eq!(db.get(addr1, 123.into()).unwrap(), 456.into());
+        assert_eq!(db.get(addr2, 123.into()).unwrap(), 789.into());
+    }
+
+    #[test]
+    fn test_memory_database_account() {
+        let dir = TempDir::new("leveldbtest").unwrap();
+        let mut db = LevelDB::new(&dir.path()).unwrap();
+        let addr = Address::from_low_u64_be(1);
+        let account = Account {
+            nonce: 1,
+            balance: 100.into(),
+            code_hash: code_hash(&[0x60, 0x00]),
+        };
+        db.set_account(addr, account.clone()).unwrap();
+        assert_eq!(db.get_account(addr).unwrap(), account);
+        assert_eq!(
+            db.get_account(Address::zero()).unwrap(),
+            Account::default()
+        );
+        db.set_account(addr, Account::default()).unwrap();
+        assert_eq!(db.get_account(addr).unwrap(), Account::default());
+    }
+
+    #[test]
+    fn test_leveldb_block_hash() {
+        let dir = TempDir::new("leveldbtest").unwrap();
+        let mut db = LevelDB::new(dir.path()).unwrap();
+        assert_eq!(db.block_hash(1).unwrap(), None);
+        db.set_block_hash(1, H256::repeat_byte(1)).unwrap();
+        assert_eq!(db.block_hash(1).unwrap(), Some(H256::repeat_byte(1)));
+        assert_eq!(db.block_hash(2).unwrap(), None);
+        assert_eq!(
+            db.get_account(Address::zero()).unwrap(),
+            Account::default()
+        );
+    }
+
+    #[test]
+    fn test_leveldb_code() {
+        let dir = TempDir::new("leveldbtest").unwrap();
+        let mut db = LevelDB::new(dir.path()).unwrap();
+        let code = vec![0x60, 0x00];
+        let hash = code_hash(&code);
+        assert_eq!(db.get_code(hash).unwrap(), None);
+        db.set_code(code.clone()).unwrap();
+        assert_eq!(db.get_code(hash).unwrap(), Some(code));
+        db.write_batch(vec![WriteOp::Code(vec![0x00])]).unwrap();
+        assert_eq!(db.get_code(code_hash(&[0x00])).unwrap(), Some(vec![0x00]));
+    }
+
+    #[test]
+    fn test_leveldb_iter() {
+        let dir = TempDir::new("leveldbtest").unwrap();
+        let mut db = LevelDB::new(dir.path()).unwrap();
+        let addr1 = Address::from_low_u64_be(1);
+        let addr2 = Address::from_low_u64_be(2);
+        db.set(addr2, 2.into(), 2.into()).unwrap();
+        db.set(addr1, 9.into(), 1.into()).unwrap();
+        db.set(addr1, 3.into(), 1.into()).unwrap();
+        db.set_block_hash(1, H256::repeat_byte(1)).unwrap();
+        let account = Account {
+            nonce: 1,
+            ..Account::default()
+        };
+        db.set_account(addr2, account.clone()).unwrap();
+        db.set_account(addr1, account).unwrap();
+        let accounts: Result<Vec<_>, _> = db.accounts().collect();
+        assert_eq!(accounts.unwrap(), vec![addr1, addr2]);
+        let storage: Result<Vec<_>, _> = db.storage(addr1).collect();
+        let expected = vec![(3.into(), 1.into()), (9.into(), 1.into())];
+        assert_eq!(storage.unwrap(), expected);
+        assert_eq!(db.storage(Address::zero()).count(), 0);
+    }
+
+    #[test]
+    fn test_leveldb_write_batch() {
+        let dir = TempDir::new("leveldbtest").unwrap();
+        let mut db = LevelDB::new(dir.path()).unwrap();
+        let addr = Address::from_low_u64_be(1);
+        db.set(addr, 1.into(), 1.into()).unwrap();
+        let account = Account {
+            nonce: 1,
+            balance: 100.into(),
+            code_hash: code_hash(&[0x00]),
+        };
+        let batch = vec![
+            WriteOp::Account(addr, account.clone()),
+            WriteOp::Storage(addr, 1.into(), 0.into()),
+            WriteOp::Storage(addr, 2.into(), 2.into()),
+        ];
+        db.write_batch(batch).unwrap();
+        assert_eq!(db.get_account(addr).unwrap(), account);
+        assert_eq!(db.get(addr, 1.into()).unwrap(), 0.into());
+        assert_eq!(db.get(addr, 2.into()).unwrap(), 2.into());
+    }
+
+    #[test]
+    fn test_leveldb_corrupted() {
+        let dir = TempDir::new("leveldbtest").unwrap();
+        let db = LevelDB::new(dir.path()).unwrap();
+        let addr = Address::from_low_u64_be(1);
+        let key = MyKey(codec::account_key(addr));
+        db.db.put(WriteOptions::new(), &key, &[1, 2, 3]).unwrap();
+        assert_eq!(db.get_account(addr), Err(DbError::Corrupted));
+    }
+
+    #[test]
+    fn test_leveldb_open_error() {
+        let dir = TempDir::new("leveldbtest").unwrap();
+        let path = dir.path().join("file");
+        std::fs::write(&path, b"not a directory").unwrap();
+        assert!(matches!(LevelDB::new(&path), Err(DbError::Backend(_))));
     }
 }
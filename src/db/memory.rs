@@ -1,29 +1,119 @@
-use crate::db::Database;
-use ethereum_types::U256;
+use crate::db::{BlockHashProvider, Database, DbError, DbIter, WriteOp};
+use crate::types::{code_hash, Account};
+use ethereum_types::{Address, H256, U256};
 use std::collections::HashMap;
 
 pub struct MemoryDB {
-    db: HashMap<U256, U256>,
+    accounts: HashMap<Address, Account>,
+    db: HashMap<(Address, U256), U256>,
+    codes: HashMap<H256, Vec<u8>>,
+    block_hashes: HashMap<u64, H256>,
+}
+
+impl Default for MemoryDB {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MemoryDB {
     /// Creates a new in-memory database.
     pub fn new() -> Self {
-        Self { db: HashMap::new() }
+        Self {
+            accounts: HashMap::new(),
+            db: HashMap::new(),
+            codes: HashMap::new(),
+            block_hashes: HashMap::new(),
+        }
+    }
+
+    /// Sets the hash of the block with the given number.
+    pub fn set_block_hash(&mut self, number: u64, hash: H256) {
+        self.block_hashes.insert(number, hash);
+    }
+}
+
+impl BlockHashProvider for MemoryDB {
+    fn block_hash(&self, number: u64) -> Result<Option<H256>, DbError> {
+        Ok(self.block_hashes.get(&number).copied())
     }
 }
 
 impl Database for MemoryDB {
-    fn get(&self, key: U256) -> U256 {
-        self.db.get(&key).cloned().unwrap_or_default()
+    fn get_account(&self, address: Address) -> Result<Account, DbError> {
+        Ok(self.accounts.get(&address).cloned().unwrap_or_default())
+    }
+
+    fn set_account(
+        &mut self,
+        address: Address,
+        account: Account,
+    ) -> Result<(), DbError> {
+        if account.is_empty() {
+            self.accounts.remove(&address);
+        } else {
+            self.accounts.insert(address, account);
+        }
+        Ok(())
     }
 
-    fn set(&mut self, key: U256, value: U256) {
+    fn get_code(&self, hash: H256) -> Result<Option<Vec<u8>>, DbError> {
+        Ok(self.codes.get(&hash).cloned())
+    }
+
+    fn set_code(&mut self, code: Vec<u8>) -> Result<(), DbError> {
+        self.codes.insert(code_hash(&code), code);
+        Ok(())
+    }
+
+    fn get(&self, address: Address, key: U256) -> Result<U256, DbError> {
+        Ok(self.db.get(&(address, key)).cloned().unwrap_or_default())
+    }
+
+    fn set(
+        &mut self,
+        address: Address,
+        key: U256,
+        value: U256,
+    ) -> Result<(), DbError> {
         if value == U256::default() {
-            self.db.remove(&key);
+            self.db.remove(&(address, key));
         } else {
-            self.db.insert(key, value);
+            self.db.insert((address, key), value);
+        }
+        Ok(())
+    }
+
+    fn accounts(&self) -> DbIter<'_, Address> {
+        let mut addresses: Vec<_> = self.accounts.keys().copied().collect();
+        addresses.sort();
+        Box::new(addresses.into_iter().map(Ok))
+    }
+
+    fn storage(&self, address: Address) -> DbIter<'_, (U256, U256)> {
+        let mut slots: Vec<_> = self
+            .db
+            .iter()
+            .filter(|((a, _), _)| *a == address)
+            .map(|((_, k), v)| (*k, *v))
+            .collect();
+        slots.sort();
+        Box::new(slots.into_iter().map(Ok))
+    }
+
+    fn write_batch(&mut self, batch: Vec<WriteOp>) -> Result<(), DbError> {
+        for op in batch {
+            match op {
+                WriteOp::Account(address, account) => {
+                    self.set_account(address, account)?
+                }
+                WriteOp::Storage(address, key, value) => {
+                    self.set(address, key, value)?
+                }
+                WriteOp::Code(code) => self.set_code(code)?,
+            }
         }
+        Ok(())
     }
 }
 
@@ -34,19 +124,117 @@ mod tests {
     #[test]
     fn test_memory_database_empty() {
         let db = MemoryDB::new();
-        assert_eq!(db.get(999.into()), 0.into());
+        assert_eq!(db.get(Address::zero(), 999.into()).unwrap(), 0.into());
+        assert_eq!(
+            db.get_account(Address::zero()).unwrap(),
+            Account::default()
+        );
     }
 
     #[test]
     fn test_memory_database_get_set() {
         let mut db = MemoryDB::new();
-        db.set(123.into(), 456.into());
-        assert_eq!(db.get(123.into()), 456.into());
-        assert_eq!(db.get(124.into()), 0.into());
-        db.set(123.into(), 789.into());
-        assert_eq!(db.get(123.into()), 789.into());
-        assert_eq!(db.get(124.into()), 0.into());
-        db.set(123.into(), 0.into());
-        assert_eq!(db.get(123.into()), 0.into());
+        let addr = Address::zero();
+        db.set(addr, 123.into(), 456.into()).unwrap();
+        assert_eq!(db.get(addr, 123.into()).unwrap(), 456.into());
+        assert_eq!(db.get(addr, 124.into()).unwrap(), 0.into());
+        db.set(addr, 123.into(), 789.into()).unwrap();
+        assert_eq!(db.get(addr, 123.into()).unwrap(), 789.into());
+        assert_eq!(db.get(addr, 124.into()).unwrap(), 0.into());
+        db.set(addr, 123.into(), 0.into()).unwrap();
+        assert_eq!(db.get(addr, 123.into()).unwrap(), 0.into());
+    }
+
+    #[test]
+    fn test_memory_database_addresses() {
+        let mut db = MemoryDB::new();
+        let addr1 = Address::from_low_u64_be(1);
+        let addr2 = Address::from_low_u64_be(2);
+        db.set(addr1, 123.into(), 456.into()).unwrap();
+        db.set(addr2, 123.into(), 789.into()).unwrap();
+        assert_eq!(db.get(addr1, 123.into()).unwrap(), 456.into());
+        assert_eq!(db.get(addr2, 123.into()).unwrap(), 789.into());
+    }
+
+    #[test]
+    fn test_memory_database_account() {
+        let mut db = MemoryDB::new();
+        let addr = Address::from_low_u64_be(1);
+        let account = Account {
+            nonce: 1,
+            balance: 100.into(),
+            code_hash: code_hash(&[0x60, 0x00]),
+        };
+        db.set_account(addr, account.clone()).unwrap();
+        assert_eq!(db.get_account(addr).unwrap(), account);
+        assert_eq!(
+            db.get_account(Address::zero()).unwrap(),
+            Account::default()
+        );
+        db.set_account(addr, Account::default()).unwrap();
+        assert_eq!(db.get_account(addr).unwrap(), Account::default());
+    }
+
+    #[test]
+    fn test_memory_database_block_hash() {
+        let mut db = MemoryDB::new();
+        assert_eq!(db.block_hash(1).unwrap(), None);
+        db.set_block_hash(1, H256::repeat_byte(1));
+        assert_eq!(db.block_hash(1).unwrap(), Some(H256::repeat_byte(1)));
+        assert_eq!(db.block_hash(2).unwrap(), None);
+    }
+
+    #[test]
+    fn test_memory_database_code() {
+        let mut db = MemoryDB::new();
+        let code = vec![0x60, 0x00];
+        let hash = code_hash(&code);
+        assert_eq!(db.get_code(hash).unwrap(), None);
+        db.set_code(code.clone()).unwrap();
+        assert_eq!(db.get_code(hash).unwrap(), Some(code));
+        db.write_batch(vec![WriteOp::Code(vec![0x00])]).unwrap();
+        assert_eq!(db.get_code(code_hash(&[0x00])).unwrap(), Some(vec![0x00]));
+    }
+
+    #[test]
+    fn test_memory_database_iter() {
+        let mut db = MemoryDB::new();
+        let addr1 = Address::from_low_u64_be(1);
+        let addr2 = Address::from_low_u64_be(2);
+        db.set(addr2, 2.into(), 2.into()).unwrap();
+        db.set(addr1, 9.into(), 1.into()).unwrap();
+        db.set(addr1, 3.into(), 1.into()).unwrap();
+        let account = Account {
+            nonce: 1,
+            ..Account::default()
+        };
+        db.set_account(addr2, account.clone()).unwrap();
+        db.set_account(addr1, account).unwrap();
+        let accounts: Result<Vec<_>, _> = db.accounts().collect();
+        assert_eq!(accounts.unwrap(), vec![addr1, addr2]);
+        let storage: Result<Vec<_>, _> = db.storage(addr1).collect();
+        let expected = vec![(3.into(), 1.into()), (9.into(), 1.into())];
+        assert_eq!(storage.unwrap(), expected);
+        assert_eq!(db.storage(Address::zero()).count(), 0);
+    }
+
+    #[test]
+    fn test_memory_database_write_batch() {
+        let mut db = MemoryDB::new();
+        let addr = Address::from_low_u64_be(1);
+        db.set(addr, 1.into(), 1.into()).unwrap();
+        let account = Account {
+            nonce: 1,
+            ..Account::default()
+        };
+        let batch = vec![
+            WriteOp::Account(addr, account.clone()),
+            WriteOp::Storage(addr, 1.into(), 0.into()),
+            WriteOp::Storage(addr, 2.into(), 2.into()),
+        ];
+        db.write_batch(batch).unwrap();
+        assert_eq!(db.get_account(addr).unwrap(), account);
+        assert_eq!(db.get(addr, 1.into()).unwrap(), 0.into());
+        assert_eq!(db.get(addr, 2.into()).unwrap(), 2.into());
     }
 }
@@ -1,7 +1,15 @@
+#[cfg(any(feature = "leveldb", feature = "sled"))]
+mod codec;
+#[cfg(feature = "leveldb")]
 mod leveldb;
 mod memory;
+#[cfg(feature = "sled")]
+mod sled;
 mod spec;
 
+#[cfg(feature = "leveldb")]
 pub use self::leveldb::LevelDB;
+#[cfg(feature = "sled")]
+pub use self::sled::SledDB;
 pub use memory::MemoryDB;
-pub use spec::Database;
+pub use spec::{BlockHashProvider, Database, DbError, DbIter, WriteOp};
@@ -0,0 +1,294 @@
+use crate::db::codec;
+use crate::db::{BlockHashProvider, Database, DbError, DbIter, WriteOp};
+use crate::types::Account;
+use ethereum_types::{Address, H256, U256};
+use std::path;
+
+impl From<sled::Error> for DbError {
+    fn from(err: sled::Error) -> Self {
+        DbError::Backend(err.to_string())
+    }
+}
+
+/// Database on the sled embedded key-value store, written in pure Rust.
+pub struct SledDB {
+    db: sled::Db,
+}
+
+impl SledDB {
+    /// Opens or creates a sled file backed database instance.
+    pub fn new(path: &path::Path) -> Result<Self, DbError> {
+        Ok(Self {
+            db: sled::open(path)?,
+        })
+    }
+
+    /// Sets the hash of the block with the given number.
+    pub fn set_block_hash(
+        &mut self,
+        number: u64,
+        hash: H256,
+    ) -> Result<(), DbError> {
+        let key = codec::block_hash_key(number);
+        self.db.insert(key, hash.as_bytes())?;
+        Ok(())
+    }
+
+    /// Returns the entries whose keys start with the given prefix, with the
+    /// prefix stripped from the keys.
+    fn scan(&self, prefix: Vec<u8>) -> DbIter<'_, (Vec<u8>, Vec<u8>)> {
+        let len = prefix.len();
+        let iter = self.db.scan_prefix(prefix).map(move |entry| {
+            let (key, value) = entry?;
+            Ok((key[len..].to_vec(), value.to_vec()))
+        });
+        Box::new(iter)
+    }
+
+    fn write(&mut self, op: WriteOp) -> Result<(), DbError> {
+        match codec::encode_op(&op) {
+            (key, None) => self.db.remove(key)?,
+            (key, Some(value)) => self.db.insert(key, value)?,
+        };
+        Ok(())
+    }
+}
+
+impl BlockHashProvider for SledDB {
+    fn block_hash(&self, number: u64) -> Result<Option<H256>, DbError> {
+        match self.db.get(codec::block_hash_key(number))? {
+            None => Ok(None),
+            Some(v) => Ok(Some(codec::decode_hash(&v)?)),
+        }
+    }
+}
+
+impl Database for SledDB {
+    fn get_account(&self, address: Address) -> Result<Account, DbError> {
+        match self.db.get(codec::account_key(address))? {
+            None => Ok(Account::default()),
+            Some(v) => codec::decode_account(&v),
+        }
+    }
+
+    fn set_account(
+        &mut self,
+        address: Address,
+        account: Account,
+    ) -> Result<(), DbError> {
+        self.write(WriteOp::Account(address, account))
+    }
+
+    fn get_code(&self, hash: H256) -> Result<Option<Vec<u8>>, DbError> {
+        Ok(self.db.get(codec::code_key(hash))?.map(|v| v.to_vec()))
+    }
+
+    fn set_code(&mut self, code: Vec<u8>) -> Result<(), DbError> {
+        self.write(WriteOp::Code(code))
+    }
+
+    fn get(&self, address: Address, key: U256) -> Result<U256, DbError> {
+        match self.db.get(codec::storage_key(address, key))? {
+            None => Ok(0.into()),
+            Some(v) => codec::decode_word(&v),
+        }
+    }
+
+    fn set(
+        &mut self,
+        address: Address,
+        key: U256,
+        value: U256,
+    ) -> Result<(), DbError> {
+        self.write(WriteOp::Storage(address, key, value))
+    }
+
+    fn accounts(&self) -> DbIter<'_, Address> {
+        let iter = self
+            .scan(vec![codec::ACCOUNT_PREFIX])
+            .map(|entry| codec::decode_address(&entry?.0));
+        Box::new(iter)
+    }
+
+    fn storage(&self, address: Address) -> DbIter<'_, (U256, U256)> {
+        let iter = self.scan(codec::storage_prefix(address)).map(|entry| {
+            let (key, value) = entry?;
+            Ok((codec::decode_slot(&key)?, codec::decode_word(&value)?))
+        });
+        Box::new(iter)
+    }
+
+    fn write_batch(&mut self, batch: Vec<WriteOp>) -> Result<(), DbError> {
+        let mut wb = sled::Batch::default();
+        for op in batch.iter() {
+            match codec::encode_op(op) {
+                (key, None) => wb.remove(key),
+                (key, Some(value)) => wb.insert(key, value),
+            }
+        }
+        self.db.apply_batch(wb)?;
+        // sled only syncs to disk periodically, make the commit durable.
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::code_hash;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_sled_database_empty() {
+        let dir = TempDir::new("sledtest").unwrap();
+        let db = SledDB::new(dir.path()).unwrap();
+        assert_eq!(db.get(Address::zero(), 999.into()).unwrap(), 0.into());
+        assert_eq!(
+            db.get_account(Address::zero()).unwrap(),
+            Account::default()
+        );
+    }
+
+    #[test]
+    fn test_sled_database_get_set() {
+        let dir = TempDir::new("sledtest").unwrap();
+        let mut db = SledDB::new(dir.path()).unwrap();
+        let addr = Address::zero();
+        db.set(addr, 123.into(), 456.into()).unwrap();
+        assert_eq!(db.get(addr, 123.into()).unwrap(), 456.into());
+        assert_eq!(db.get(addr, 124.into()).unwrap(), 0.into());
+        db.set(addr, 123.into(), 789.into()).unwrap();
+        assert_eq!(db.get(addr, 123.into()).unwrap(), 789.into());
+        assert_eq!(db.get(addr, 124.into()).unwrap(), 0.into());
+        db.set(addr, 123.into(), 0.into()).unwrap();
+        assert_eq!(db.get(addr, 123.into()).unwrap(), 0.into());
+    }
+
+    #[test]
+    fn test_sled_database_addresses() {
+        let dir = TempDir::new("sledtest").unwrap();
+        let mut db = SledDB::new(dir.path()).unwrap();
+        let addr1 = Address::from_low_u64_be(1);
+        let addr2 = Address::from_low_u64_be(2);
+        db.set(addr1, 123.into(), 456.into()).unwrap();
+        db.set(addr2, 123.into(), 789.into()).unwrap();
+        assert_eq!(db.get(addr1, 123.into()).unwrap(), 456.into());
+        assert_eq!(db.get(addr2, 123.into()).unwrap(), 789.into());
+    }
+
+    #[test]
+    fn test_sled_database_account() {
+        let dir = TempDir::new("sledtest").unwrap();
+        let mut db = SledDB::new(dir.path()).unwrap();
+        let addr = Address::from_low_u64_be(1);
+        let account = Account {
+            nonce: 1,
+            balance: 100.into(),
+            code_hash: code_hash(&[0x60, 0x00]),
+        };
+        db.set_account(addr, account.clone()).unwrap();
+        assert_eq!(db.get_account(addr).unwrap(), account);
+        assert_eq!(
+            db.get_account(Address::zero()).unwrap(),
+            Account::default()
+        );
+        db.set_account(addr, Account::default()).unwrap();
+        assert_eq!(db.get_account(addr).unwrap(), Account::default());
+    }
+
+    #[test]
+    fn test_sled_block_hash() {
+        let dir = TempDir::new("sledtest").unwrap();
+        let mut db = SledDB::new(dir.path()).unwrap();
+        assert_eq!(db.block_hash(1).unwrap(), None);
+        db.set_block_hash(1, H256::repeat_byte(1)).unwrap();
+        assert_eq!(db.block_hash(1).unwrap(), Some(H256::repeat_byte(1)));
+        assert_eq!(db.block_hash(2).unwrap(), None);
+        assert_eq!(
+            db.get_account(Address::zero()).unwrap(),
+            Account::default()
+        );
+    }
+
+    #[test]
+    fn test_sled_code() {
+        let dir = TempDir::new("sledtest").unwrap();
+        let mut db = SledDB::new(dir.path()).unwrap();
+        let code = vec![0x60, 0x00];
+        let hash = code_hash(&code);
+        assert_eq!(db.get_code(hash).unwrap(), None);
+        db.set_code(code.clone()).unwrap();
+        assert_eq!(db.get_code(hash).unwrap(), Some(code));
+        db.write_batch(vec![WriteOp::Code(vec![0x00])]).unwrap();
+        assert_eq!(db.get_code(code_hash(&[0x00])).unwrap(), Some(vec![0x00]));
+    }
+
+    #[test]
+    fn test_sled_iter() {
+        let dir = TempDir::new("sledtest").unwrap();
+        let mut db = SledDB::new(dir.path()).unwrap();
+        let addr1 = Address::from_low_u64_be(1);
+        let addr2 = Address::from_low_u64_be(2);
+        db.set(addr2, 2.into(), 2.into()).unwrap();
+        db.set(addr1, 9.into(), 1.into()).unwrap();
+        db.set(addr1, 3.into(), 1.into()).unwrap();
+        db.set_block_hash(1, H256::repeat_byte(1)).unwrap();
+        let account = Account {
+            nonce: 1,
+            ..Account::default()
+        };
+        db.set_account(addr2, account.clone()).unwrap();
+        db.set_account(addr1, account).unwrap();
+        let accounts: Result<Vec<_>, _> = db.accounts().collect();
+        assert_eq!(accounts.unwrap(), vec![addr1, addr2]);
+        let storage: Result<Vec<_>, _> = db.storage(addr1).collect();
+        let expected = vec![(3.into(), 1.into()), (9.into(), 1.into())];
+        assert_eq!(storage.unwrap(), expected);
+        assert_eq!(db.storage(Address::zero()).count(), 0);
+    }
+
+    #[test]
+    fn test_sled_write_batch() {
+        let dir = TempDir::new("sledtest").unwrap();
+        let mut db = SledDB::new(dir.path()).unwrap();
+        let addr = Address::from_low_u64_be(1);
+        db.set(addr, 1.into(), 1.into()).unwrap();
+        let account = Account {
+            nonce: 1,
+            balance: 100.into(),
+            code_hash: code_hash(&[0x00]),
+        };
+        let batch = vec![
+            WriteOp::Account(addr, account.clone()),
+            WriteOp::Storage(addr, 1.into(), 0.into()),
+            WriteOp::Storage(addr, 2.into(), 2.into()),
+        ];
+        db.write_batch(batch).unwrap();
+        assert_eq!(db.get_account(addr).unwrap(), account);
+        assert_eq!(db.get(addr, 1.into()).unwrap(), 0.into());
+        assert_eq!(db.get(addr, 2.into()).unwrap(), 2.into());
+    }
+
+    #[test]
+    fn test_sled_corrupted() {
+        let dir = TempDir::new("sledtest").unwrap();
+        let db = SledDB::new(dir.path()).unwrap();
+        let addr = Address::from_low_u64_be(1);
+        db.db.insert(codec::account_key(addr), &[1, 2, 3]).unwrap();
+        assert_eq!(db.get_account(addr), Err(DbError::Corrupted));
+    }
+
+    #[test]
+    fn test_sled_reopen() {
+        let dir = TempDir::new("sledtest").unwrap();
+        let addr = Address::from_low_u64_be(1);
+        {
+            let mut db = SledDB::new(dir.path()).unwrap();
+            let batch = vec![WriteOp::Storage(addr, 1.into(), 1.into())];
+            db.write_batch(batch).unwrap();
+        }
+        let db = SledDB::new(dir.path()).unwrap();
+        assert_eq!(db.get(addr, 1.into()).unwrap(), 1.into());
+    }
+}
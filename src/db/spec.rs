@@ -1,9 +1,83 @@
-use ethereum_types::U256;
+use crate::types::Account;
+use ethereum_types::{Address, H256, U256};
+use std::fmt;
+
+/// Error returned by a database backend.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DbError {
+    /// The underlying storage failed, e.g. on I/O or when opening.
+    Backend(String),
+    /// A stored value could not be decoded.
+    Corrupted,
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbError::Backend(msg) => write!(f, "database error: {}", msg),
+            DbError::Corrupted => write!(f, "database value is corrupted"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+/// A single change written to the database as part of a batch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WriteOp {
+    /// Sets the account at the address.
+    Account(Address, Account),
+    /// Sets the value at the key slot of the account.
+    Storage(Address, U256, U256),
+    /// Stores the code under its hash.
+    Code(Vec<u8>),
+}
+
+/// Iterator over database entries in ascending key order.
+pub type DbIter<'a, T> = Box<dyn Iterator<Item = Result<T, DbError>> + 'a>;
+
+/// Source of the hashes of past blocks, used by the BLOCKHASH opcode. It is
+/// given to the VM separately from its database, see VMBuilder::block_hashes.
+pub trait BlockHashProvider {
+    /// Returns the hash of the block with the given number, if known.
+    fn block_hash(&self, number: u64) -> Result<Option<H256>, DbError>;
+}
 
 pub trait Database {
-    /// Returns the value at the specified key slot.
-    fn get(&self, key: U256) -> U256;
+    /// Returns the account at the specified address.
+    fn get_account(&self, address: Address) -> Result<Account, DbError>;
+
+    /// Sets the account at the specified address.
+    fn set_account(
+        &mut self,
+        address: Address,
+        account: Account,
+    ) -> Result<(), DbError>;
+
+    /// Returns the code with the given hash, if known.
+    fn get_code(&self, hash: H256) -> Result<Option<Vec<u8>>, DbError>;
+
+    /// Stores the code under its keccak hash.
+    fn set_code(&mut self, code: Vec<u8>) -> Result<(), DbError>;
+
+    /// Returns the value at the specified key slot of the account.
+    fn get(&self, address: Address, key: U256) -> Result<U256, DbError>;
+
+    /// Sets the value at the specified key slot of the account.
+    fn set(
+        &mut self,
+        address: Address,
+        key: U256,
+        value: U256,
+    ) -> Result<(), DbError>;
+
+    /// Returns the addresses of all the non-empty accounts.
+    fn accounts(&self) -> DbIter<'_, Address>;
+
+    /// Returns the non-zero key-values of the account's storage.
+    fn storage(&self, address: Address) -> DbIter<'_, (U256, U256)>;
 
-    /// Sets the value at the specified key slot.
-    fn set(&mut self, key: U256, value: U256);
+    /// Applies all the given changes atomically, either all or none of them
+    /// are persisted.
+    fn write_batch(&mut self, batch: Vec<WriteOp>) -> Result<(), DbError>;
 }
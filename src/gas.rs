@@ -0,0 +1,197 @@
+use crate::spec::Spec;
+use crate::types::Error;
+
+// Fee schedule, see Appendix G of the Ethereum yellow paper. Costs that
+// changed between hardforks are in the Schedule of each Spec instead.
+pub const ZERO: u64 = 0;
+pub const BASE: u64 = 2;
+pub const VERYLOW: u64 = 3;
+pub const LOW: u64 = 5;
+pub const MID: u64 = 8;
+pub const HIGH: u64 = 10;
+pub const JUMPDEST: u64 = 1;
+pub const BLOCKHASH: u64 = 20;
+pub const SSET: u64 = 20000;
+pub const EXP: u64 = 10;
+pub const MEMORY: u64 = 3;
+pub const KECCAK256: u64 = 30;
+pub const KECCAK256WORD: u64 = 6;
+pub const COPY: u64 = 3;
+pub const WARM_STORAGE_READ: u64 = 100;
+pub const COLD_SLOAD: u64 = 2100;
+pub const COLD_ACCOUNT_ACCESS: u64 = 2600;
+pub const LOG: u64 = 375;
+pub const LOGDATA: u64 = 8;
+pub const LOGTOPIC: u64 = 375;
+pub const CALLVALUE: u64 = 9000;
+pub const CALLSTIPEND: u64 = 2300;
+pub const NEWACCOUNT: u64 = 25000;
+pub const CREATE: u64 = 32000;
+pub const CODEDEPOSIT: u64 = 200;
+pub const INITCODEWORD: u64 = 2;
+pub const TRANSACTION: u64 = 21000;
+pub const TXCREATE: u64 = 32000;
+pub const TXDATAZERO: u64 = 4;
+
+/// Gas costs that changed between hardforks, see Spec::schedule.
+pub struct Schedule {
+    pub sload: u64,
+    pub balance: u64,
+    pub extcode: u64,
+    pub extcodehash: u64,
+    pub call: u64,
+    pub expbyte: u64,
+    pub sreset: u64,
+    pub sclear_refund: u64,
+    pub txdatanonzero: u64,
+    pub max_refund_quotient: u64,
+}
+
+/// Returns the number of 32-byte words needed to cover the given length.
+pub fn words(len: usize) -> u64 {
+    (len as u64).div_ceil(32)
+}
+
+/// Returns the cost of a log with the given number of topics and data
+/// length, failing with out of gas if it does not fit in a u64.
+pub fn log_cost(topics: u64, len: usize) -> Result<u64, Error> {
+    LOGDATA
+        .checked_mul(len as u64)
+        .and_then(|cost| cost.checked_add(LOG + LOGTOPIC * topics))
+        .ok_or(Error::OutOfGas)
+}
+
+/// Returns the total cost of a memory of the given size in words.
+pub fn memory_cost(words: u64) -> u64 {
+    MEMORY * words + words * words / 512
+}
+
+/// Returns the gas charged before execution for the given calldata.
+pub fn intrinsic(spec: Spec, calldata: &[u8]) -> u64 {
+    let nonzero = calldata.iter().filter(|byte| **byte != 0).count() as u64;
+    let zero = calldata.len() as u64 - nonzero;
+    let txdatanonzero = spec.schedule().txdatanonzero;
+    TRANSACTION + TXDATAZERO * zero + txdatanonzero * nonzero
+}
+
+/// Returns the gas charged before executing the given init code.
+pub fn intrinsic_create(spec: Spec, initcode: &[u8]) -> u64 {
+    let mut cost = intrinsic(spec, initcode);
+    if spec.enabled(Spec::Homestead) {
+        cost += TXCREATE;
+    }
+    if spec.enabled(Spec::Shanghai) {
+        cost += INITCODEWORD * words(initcode.len());
+    }
+    cost
+}
+
+/// Returns the gas used after applying the refund, capped at a fraction of
+/// the usage that depends on the fork.
+pub fn apply_refund(spec: Spec, used: u64, refund: u64) -> u64 {
+    used - u64::min(refund, used / spec.schedule().max_refund_quotient)
+}
+
+pub struct Gas {
+    limit: u64,
+    used: u64,
+    refund: i64,
+}
+
+impl Gas {
+    /// Creates a new gas meter with the given limit.
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            used: 0,
+            refund: 0,
+        }
+    }
+
+    /// Returns the total amount of gas spent so far.
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    /// Returns the amount of gas still available for execution.
+    pub fn remaining(&self) -> u64 {
+        self.limit - self.used
+    }
+
+    /// Consumes the given amount of gas or fails if not enough is left.
+    pub fn charge(&mut self, amount: u64) -> Result<(), Error> {
+        if amount > self.remaining() {
+            self.used = self.limit;
+            return Err(Error::OutOfGas);
+        }
+        self.used += amount;
+        Ok(())
+    }
+
+    /// Gives back the given amount of unspent gas from a child frame.
+    pub fn return_gas(&mut self, amount: u64) {
+        self.used -= amount;
+    }
+
+    /// Adds the given amount to the refund counter. The amount is negative
+    /// when a refund granted earlier in the transaction is taken back.
+    pub fn refund(&mut self, amount: i64) {
+        self.refund += amount;
+    }
+
+    /// Returns the amount of gas accumulated in the refund counter.
+    pub fn refunded(&self) -> i64 {
+        self.refund
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gas_charge() {
+        let mut gas = Gas::new(100);
+        assert_eq!(gas.charge(60), Ok(()));
+        assert_eq!(gas.used(), 60);
+        assert_eq!(gas.remaining(), 40);
+        assert_eq!(gas.charge(41), Err(Error::OutOfGas));
+        assert_eq!(gas.used(), 100);
+        assert_eq!(gas.remaining(), 0);
+    }
+
+    #[test]
+    fn test_gas_refund() {
+        let mut gas = Gas::new(100000);
+        gas.charge(30000).unwrap();
+        gas.refund(15000);
+        assert_eq!(gas.refunded(), 15000);
+        assert_eq!(apply_refund(Spec::Frontier, gas.used(), 15000), 15000);
+        gas.refund(15000);
+        assert_eq!(gas.refunded(), 30000);
+        assert_eq!(apply_refund(Spec::Frontier, gas.used(), 30000), 15000);
+        assert_eq!(apply_refund(Spec::London, gas.used(), 30000), 24000);
+        gas.refund(-20000);
+        assert_eq!(gas.refunded(), 10000);
+    }
+
+    #[test]
+    fn test_memory_cost() {
+        assert_eq!(words(0), 0);
+        assert_eq!(words(1), 1);
+        assert_eq!(words(32), 1);
+        assert_eq!(words(33), 2);
+        assert_eq!(memory_cost(1), 3);
+        assert_eq!(memory_cost(32), 98);
+        assert_eq!(intrinsic(Spec::Frontier, &[0, 1, 0]), 21000 + 4 + 68 + 4);
+        assert_eq!(intrinsic(Spec::Istanbul, &[0, 1, 0]), 21000 + 4 + 16 + 4);
+        assert_eq!(intrinsic_create(Spec::Frontier, &[0; 33]), 21000 + 33 * 4);
+        assert_eq!(intrinsic_create(Spec::London, &[0; 33]), 53000 + 33 * 4);
+        assert_eq!(
+            intrinsic_create(Spec::Shanghai, &[0; 33]),
+            53000 + 33 * 4 + 2 * 2
+        );
+        assert_eq!(log_cost(2, 10), Ok(375 + 2 * 375 + 8 * 10));
+        assert_eq!(log_cost(0, 1 << 62), Err(Error::OutOfGas));
+    }
+}
@@ -27,8 +27,6 @@ pub const MIN_NEGATIVE_VALUE: U256 = U256([
 
 const SIGN_BITMASK_U64: u64 = 0x8000000000000000;
 const FLIPH_BITMASK_U64: u64 = 0x7FFFFFFFFFFFFFFF;
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
-pub struct I256(pub Sign, pub U256);
 
 #[inline(always)]
 pub fn i256_sign<const DO_TWO_COMPL: bool>(val: &mut U256) -> Sign {
@@ -193,7 +191,7 @@ pub mod div_u256 {
                 q_hat
             } else {
                 // here q_hat >= q_j >= q_hat - 1
-                u64::max_value()
+                u64::MAX
             };
 
             // ex. 20:
@@ -401,7 +399,10 @@ pub fn i256_mod(mut first: U256, mut second: U256) -> U256 {
         return U256::zero();
     }
 
-    let _ = i256_sign::<true>(&mut second);
+    let second_sign = i256_sign::<true>(&mut second);
+    if second_sign == Sign::Zero {
+        return U256::zero();
+    }
     let mut r = first % second;
     u256_remove_sign(&mut r);
     if r.is_zero() {
@@ -414,11 +415,40 @@ pub fn i256_mod(mut first: U256, mut second: U256) -> U256 {
     }
 }
 
+#[inline(always)]
+pub fn i256_signextend(ext: U256, value: U256) -> U256 {
+    if ext >= U256::from(31) {
+        return value;
+    }
+    let bit = ext.as_usize() * 8 + 7;
+    let mask = (U256::one() << bit) - U256::one();
+    if value.bit(bit) {
+        value | !mask
+    } else {
+        value & mask
+    }
+}
+
+#[inline(always)]
+pub fn i256_sar(shift: U256, mut value: U256) -> U256 {
+    let value_sign = i256_sign::<true>(&mut value);
+    if value_sign == Sign::Minus {
+        if shift >= U256::from(255) {
+            return U256::MAX;
+        }
+        // Rounds toward negative infinity: -((|v| - 1) / 2^shift + 1).
+        two_compl(((value - U256::one()) >> shift.as_usize()) + U256::one())
+    } else if shift >= U256::from(256) {
+        U256::zero()
+    } else {
+        value >> shift.as_usize()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use core::num::Wrapping;
-    use primitive_types::U256;
 
     #[test]
     fn div_i256() {
@@ -461,4 +491,64 @@ mod tests {
         }
         println!("TIME:{:?}", time.elapsed());
     }
+
+    #[test]
+    fn signextend_i256() {
+        let neg_one = U256::MAX;
+        let x = U256::from(0xff);
+        assert_eq!(i256_signextend(0.into(), x), neg_one);
+        assert_eq!(i256_signextend(0.into(), 0x7f.into()), 0x7f.into());
+        assert_eq!(i256_signextend(0.into(), 0x17f.into()), 0x7f.into());
+        assert_eq!(i256_signextend(1.into(), x), x);
+        assert_eq!(
+            i256_signextend(1.into(), 0x8000.into()),
+            !U256::from(0x7fff)
+        );
+        assert_eq!(i256_signextend(0.into(), U256::zero()), U256::zero());
+        assert_eq!(
+            i256_signextend(30.into(), MIN_NEGATIVE_VALUE >> 8),
+            !(U256::MAX >> 9)
+        );
+        assert_eq!(i256_signextend(30.into(), U256::MAX >> 9), U256::MAX >> 9);
+        assert_eq!(i256_signextend(31.into(), x), x);
+        assert_eq!(i256_signextend(31.into(), neg_one), neg_one);
+        assert_eq!(i256_signextend(32.into(), x), x);
+        assert_eq!(i256_signextend(255.into(), x), x);
+        assert_eq!(i256_signextend(256.into(), x), x);
+        assert_eq!(i256_signextend(U256::MAX, x), x);
+    }
+
+    #[test]
+    fn sar_i256() {
+        let neg_one = U256::MAX;
+        let neg_sixteen = two_compl(16.into());
+        assert_eq!(i256_sar(0.into(), 1.into()), 1.into());
+        assert_eq!(i256_sar(1.into(), 1.into()), 0.into());
+        assert_eq!(i256_sar(4.into(), 0x100.into()), 0x10.into());
+        assert_eq!(i256_sar(0.into(), neg_one), neg_one);
+        assert_eq!(i256_sar(1.into(), neg_one), neg_one);
+        assert_eq!(i256_sar(2.into(), neg_sixteen), two_compl(4.into()));
+        assert_eq!(i256_sar(5.into(), neg_sixteen), neg_one);
+        assert_eq!(
+            i256_sar(3.into(), two_compl(17.into())),
+            two_compl(3.into())
+        );
+        assert_eq!(
+            i256_sar(1.into(), MIN_NEGATIVE_VALUE),
+            two_compl(MIN_NEGATIVE_VALUE >> 1)
+        );
+        assert_eq!(
+            i256_sar(254.into(), MIN_NEGATIVE_VALUE),
+            two_compl(2.into())
+        );
+        assert_eq!(i256_sar(255.into(), MIN_NEGATIVE_VALUE), neg_one);
+        assert_eq!(i256_sar(255.into(), U256::MAX >> 1), 0.into());
+        assert_eq!(i256_sar(254.into(), U256::MAX >> 1), 1.into());
+        assert_eq!(i256_sar(256.into(), MIN_NEGATIVE_VALUE), neg_one);
+        assert_eq!(i256_sar(256.into(), U256::MAX >> 1), 0.into());
+        assert_eq!(i256_sar(257.into(), neg_sixteen), neg_one);
+        assert_eq!(i256_sar(U256::MAX, neg_sixteen), neg_one);
+        assert_eq!(i256_sar(U256::MAX, 16.into()), 0.into());
+        assert_eq!(i256_sar(U256::MAX, 0.into()), 0.into());
+    }
 }
@@ -1,6 +1,6 @@
-use crate::types::Env;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use tinyevm::Env;
 
 pub trait IO {
     /// Returns the EVM code to process.
@@ -37,3 +37,71 @@ impl IO for FileIO {
         self.envs.pop()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_file_io() {
+        let dir = TempDir::new("test_file_io").unwrap();
+        let path = dir.path().join("data.json");
+        let json = r#"{
+            "code": "6000",
+            "envs": [
+                {
+                    "chainid": "0x1",
+                    "block": {
+                        "number": "0x10",
+                        "coinbase": "0x00000000000000000000000000000000000000c0",
+                        "basefee": "0x7"
+                    },
+                    "tx": {
+                        "caller": "0x00000000000000000000000000000000000000ca",
+                        "gaslimit": 100000,
+                        "value": "0x0",
+                        "calldata": "abcd"
+                    }
+                },
+                {
+                    "chainid": "0x1",
+                    "block": {},
+                    "tx": {
+                        "caller": "0x00000000000000000000000000000000000000ca",
+                        "gasprice": "0x2",
+                        "gaslimit": 50000,
+                        "value": "0x1",
+                        "calldata": ""
+                    }
+                },
+                {
+                    "chainid": "0x1",
+                    "block": {},
+                    "tx": {
+                        "caller": "0x00000000000000000000000000000000000000ca"
+                    }
+                }
+            ]
+        }"#;
+        std::fs::write(&path, json).unwrap();
+        let mut fio = FileIO::new(&path);
+        assert_eq!(fio.get_code(), vec![0x60, 0x00]);
+        let env = fio.get_next_env().unwrap();
+        assert_eq!(env.block.number, 16.into());
+        assert_eq!(env.block.coinbase.to_low_u64_be(), 0xc0);
+        assert_eq!(env.block.basefee, 7.into());
+        assert_eq!(env.block.timestamp, 0.into());
+        assert_eq!(env.tx.gasprice, 0.into());
+        assert_eq!(env.tx.calldata, vec![0xab, 0xcd]);
+        let env = fio.get_next_env().unwrap();
+        assert_eq!(env.tx.gasprice, 2.into());
+        assert_eq!(env.tx.gaslimit, 50000);
+        // Transaction fields other than the caller may be left out.
+        let env = fio.get_next_env().unwrap();
+        assert_eq!(env.tx.gaslimit, 0);
+        assert_eq!(env.tx.value, 0.into());
+        assert!(env.tx.calldata.is_empty());
+        assert!(fio.get_next_env().is_none());
+    }
+}
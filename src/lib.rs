@@ -0,0 +1,27 @@
+//! A tiny Ethereum Virtual Machine.
+//!
+//! A VM runs the transactions of an Env against the accounts of a Database
+//! and returns a RunResult, see VM::builder to create one.
+
+pub mod abi;
+mod analysis;
+pub mod db;
+mod gas;
+mod i256;
+mod mem;
+pub mod precompile;
+mod runtime;
+pub mod spec;
+mod stack;
+mod state;
+pub mod types;
+pub mod vm;
+
+pub use db::{Database, DbError, MemoryDB};
+pub use gas::Gas;
+pub use precompile::{Precompile, PrecompileCall};
+pub use runtime::{create2_address, create_address};
+pub use spec::Spec;
+pub use state::State;
+pub use types::{Account, BlockEnv, Env, Error, Log, RunResult, TxEnv};
+pub use vm::{VMBuilder, VM};
@@ -1,3 +1,4 @@
+use crate::gas;
 use crate::types::Error;
 use ethereum_types::U256;
 
@@ -14,11 +15,14 @@ impl Mem {
 
     /// Returns the size of the current memory.
     pub fn size(&self) -> usize {
-        return self.0.len();
+        self.0.len()
     }
 
     /// Resizes the memory buffer to allow accessing the given location.
     pub fn resize_for(&mut self, key: usize) -> Result<(), Error> {
+        if key == 0 {
+            return Ok(());
+        }
         let bound = (((key - 1) / 32) + 1) * 32;
         if bound > self.0.len() {
             self.0.resize(bound, 0);
@@ -26,13 +30,36 @@ impl Mem {
         Ok(())
     }
 
+    /// Returns the gas cost to expand the memory to cover the specified area.
+    pub fn expansion_cost(
+        &self,
+        start: usize,
+        len: usize,
+    ) -> Result<u64, Error> {
+        if len == 0 {
+            return Ok(0);
+        }
+        let end = start.checked_add(len).ok_or(Error::MemoryOverflow)?;
+        if end > MAX_SIZE {
+            return Err(Error::MemoryOverflow);
+        }
+        let cur = gas::words(self.0.len());
+        let new = gas::words(end);
+        if new > cur {
+            Ok(gas::memory_cost(new) - gas::memory_cost(cur))
+        } else {
+            Ok(0)
+        }
+    }
+
     /// Stores the given u8 value to the location at the specified key.
     pub fn mstores(&mut self, key: usize, value: u8) -> Result<(), Error> {
         if key >= MAX_SIZE {
             return Err(Error::MemoryOverflow);
         }
         self.resize_for(key)?;
-        Ok(self.0[key] = value)
+        self.0[key] = value;
+        Ok(())
     }
 
     /// Stores the given 256 value to the location at the specified key.
@@ -41,7 +68,43 @@ impl Mem {
             return Err(Error::MemoryOverflow);
         }
         self.resize_for(key + WORD_SIZE)?;
-        Ok(value.to_big_endian(&mut self.0[key..key + WORD_SIZE]))
+        value.to_big_endian(&mut self.0[key..key + WORD_SIZE]);
+        Ok(())
+    }
+
+    /// Writes the given bytes to the memory starting at the specified key.
+    pub fn mwrite(&mut self, key: usize, data: &[u8]) -> Result<(), Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let end = key.checked_add(data.len()).ok_or(Error::MemoryOverflow)?;
+        if end > MAX_SIZE {
+            return Err(Error::MemoryOverflow);
+        }
+        self.resize_for(end)?;
+        self.0[key..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Copies the area of the given length from src to dst, which may overlap.
+    pub fn mcopy(
+        &mut self,
+        dst: usize,
+        src: usize,
+        len: usize,
+    ) -> Result<(), Error> {
+        if len == 0 {
+            return Ok(());
+        }
+        let end = usize::max(dst, src)
+            .checked_add(len)
+            .ok_or(Error::MemoryOverflow)?;
+        if end > MAX_SIZE {
+            return Err(Error::MemoryOverflow);
+        }
+        self.resize_for(end)?;
+        self.0.copy_within(src..src + len, dst);
+        Ok(())
     }
 
     /// Loads the value from the location at the specified key.
@@ -85,4 +148,41 @@ mod tests {
         assert_eq!(mem.mload(1001), Ok(2560.into()));
         assert_eq!(mem.size(), 1056);
     }
+
+    #[test]
+    fn test_mwrite() {
+        let mut mem = Mem::new();
+        mem.mwrite(30, &[1, 2, 3]).unwrap();
+        assert_eq!(mem.size(), 64);
+        assert_eq!(mem.mview(29, 5), Ok(&[0, 1, 2, 3, 0][..]));
+        assert_eq!(mem.mwrite(MAX_SIZE, &[1]), Err(Error::MemoryOverflow));
+        assert_eq!(mem.mwrite(MAX_SIZE, &[]), Ok(()));
+    }
+
+    #[test]
+    fn test_expansion_cost() {
+        let mut mem = Mem::new();
+        assert_eq!(mem.expansion_cost(0, 0), Ok(0));
+        assert_eq!(mem.expansion_cost(1000000, 0), Ok(0));
+        assert_eq!(mem.expansion_cost(0, 32), Ok(3));
+        assert_eq!(mem.expansion_cost(0, 33), Ok(6));
+        assert_eq!(mem.expansion_cost(MAX_SIZE, 1), Err(Error::MemoryOverflow));
+        mem.mstore(0, 1.into()).unwrap();
+        assert_eq!(mem.expansion_cost(0, 32), Ok(0));
+        assert_eq!(mem.expansion_cost(16, 32), Ok(3));
+    }
+
+    #[test]
+    fn test_mcopy() {
+        let mut mem = Mem::new();
+        mem.mwrite(0, &[1, 2, 3, 4]).unwrap();
+        mem.mcopy(1, 0, 3).unwrap();
+        assert_eq!(mem.mview(0, 4), Ok(&[1, 1, 2, 3][..]));
+        mem.mcopy(0, 2, 2).unwrap();
+        assert_eq!(mem.mview(0, 4), Ok(&[2, 3, 2, 3][..]));
+        mem.mcopy(40, 0, 2).unwrap();
+        assert_eq!(mem.size(), 64);
+        assert_eq!(mem.mcopy(MAX_SIZE, 0, 1), Err(Error::MemoryOverflow));
+        assert_eq!(mem.mcopy(MAX_SIZE, 0, 0), Ok(()));
+    }
 }
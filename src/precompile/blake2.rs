@@ -0,0 +1,148 @@
+use crate::gas::Gas;
+use crate::spec::Spec;
+use crate::types::Error;
+
+const INPUT_SIZE: usize = 213;
+const ROUND: u64 = 1;
+
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// Runs the BLAKE2b compression function F, see EIP-152. The input is
+/// rounds ++ h ++ m ++ t ++ f, where all but rounds are little-endian.
+pub fn blake2f(input: &[u8], gas: &mut Gas, _: Spec) -> Result<Vec<u8>, Error> {
+    if input.len() != INPUT_SIZE {
+        return Err(Error::PrecompileFailed);
+    }
+    let rounds = u32::from_be_bytes(input[..4].try_into().unwrap());
+    gas.charge(ROUND * rounds as u64)?;
+    let last = match input[212] {
+        0 => false,
+        1 => true,
+        _ => return Err(Error::PrecompileFailed),
+    };
+    let word = |i: usize| {
+        let start = 4 + 8 * i;
+        u64::from_le_bytes(input[start..start + 8].try_into().unwrap())
+    };
+    let mut h: [u64; 8] = std::array::from_fn(word);
+    let m: [u64; 16] = std::array::from_fn(|i| word(8 + i));
+    let t = [word(24), word(25)];
+    compress(rounds, &mut h, &m, t, last);
+    Ok(h.iter().flat_map(|word| word.to_le_bytes()).collect())
+}
+
+fn compress(
+    rounds: u32,
+    h: &mut [u64; 8],
+    m: &[u64; 16],
+    t: [u64; 2],
+    last: bool,
+) {
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(h);
+    v[8..].copy_from_slice(&IV);
+    v[12] ^= t[0];
+    v[13] ^= t[1];
+    if last {
+        v[14] = !v[14];
+    }
+    for i in 0..rounds as usize {
+        let s = &SIGMA[i % 10];
+        mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+fn mix(
+    v: &mut [u64; 16],
+    a: usize,
+    b: usize,
+    c: usize,
+    d: usize,
+    x: u64,
+    y: u64,
+) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vector 5 of EIP-152, the compression of "abc" in 12 rounds.
+    const INPUT: &str = concat!(
+        "0000000c48c9bdf267e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f",
+        "3af54fa5d182e6ad7f520e511f6c3e2b8c68059b6bbd41fbabd9831f79217e13",
+        "19cde05b61626300000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "000000000300000000000000000000000000000001"
+    );
+
+    #[test]
+    fn test_blake2f() {
+        let input = hex::decode(INPUT).unwrap();
+        let mut gas = Gas::new(100);
+        let output = blake2f(&input, &mut gas, Spec::LATEST).unwrap();
+        let expected = concat!(
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1",
+            "7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+        );
+        assert_eq!(hex::encode(output), expected);
+        assert_eq!(gas.used(), 12);
+    }
+
+    #[test]
+    fn test_blake2f_invalid() {
+        let mut input = hex::decode(INPUT).unwrap();
+        let mut gas = Gas::new(100);
+        let res = blake2f(&input[1..], &mut gas, Spec::LATEST);
+        assert_eq!(res, Err(Error::PrecompileFailed));
+        input[212] = 2;
+        let res = blake2f(&input, &mut gas, Spec::LATEST);
+        assert_eq!(res, Err(Error::PrecompileFailed));
+        input[212] = 1;
+        input[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        let res = blake2f(&input, &mut gas, Spec::LATEST);
+        assert_eq!(res, Err(Error::OutOfGas));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::gas::Gas;
+use crate::precompile::read;
+use crate::spec::Spec;
+use crate::types::Error;
+use substrate_bn::{AffineG1, AffineG2, Fq, Fq2, Fr, Group, Gt, G1, G2};
+
+// Costs were lowered in Istanbul, see EIP-1108.
+const ADD_BYZANTIUM: u64 = 500;
+const ADD_ISTANBUL: u64 = 150;
+const MUL_BYZANTIUM: u64 = 40000;
+const MUL_ISTANBUL: u64 = 6000;
+const PAIRING_BYZANTIUM: u64 = 100000;
+const PAIRING_ISTANBUL: u64 = 45000;
+const PAIRING_PAIR_BYZANTIUM: u64 = 80000;
+const PAIRING_PAIR_ISTANBUL: u64 = 34000;
+const PAIR_SIZE: usize = 192;
+
+/// Adds two points on the alt_bn128 curve, see EIP-196.
+pub fn add(input: &[u8], gas: &mut Gas, spec: Spec) -> Result<Vec<u8>, Error> {
+    if spec.enabled(Spec::Istanbul) {
+        gas.charge(ADD_ISTANBUL)?;
+    } else {
+        gas.charge(ADD_BYZANTIUM)?;
+    }
+    let input = read(input, 0, 128);
+    let p1 = read_g1(&input[..64])?;
+    let p2 = read_g1(&input[64..])?;
+    Ok(write_g1(p1 + p2))
+}
+
+/// Multiplies a point on the alt_bn128 curve by a scalar, see EIP-196.
+pub fn mul(input: &[u8], gas: &mut Gas, spec: Spec) -> Result<Vec<u8>, Error> {
+    if spec.enabled(Spec::Istanbul) {
+        gas.charge(MUL_ISTANBUL)?;
+    } else {
+        gas.charge(MUL_BYZANTIUM)?;
+    }
+    let input = read(input, 0, 96);
+    let p = read_g1(&input[..64])?;
+    let scalar = substrate_bn::arith::U256::from_slice(&input[64..])
+        .map_err(|_| Error::PrecompileFailed)?;
+    Ok(write_g1(p * Fr::new_mul_factor(scalar)))
+}
+
+/// Checks that the product of the pairings of the given G1 and G2 points is
+/// one, see EIP-197. Returns one as a word if it is, zero otherwise.
+pub fn pairing(
+    input: &[u8],
+    gas: &mut Gas,
+    spec: Spec,
+) -> Result<Vec<u8>, Error> {
+    let pairs = (input.len() / PAIR_SIZE) as u64;
+    if spec.enabled(Spec::Istanbul) {
+        gas.charge(PAIRING_ISTANBUL + PAIRING_PAIR_ISTANBUL * pairs)?;
+    } else {
+        gas.charge(PAIRING_BYZANTIUM + PAIRING_PAIR_BYZANTIUM * pairs)?;
+    }
+    if !input.len().is_multiple_of(PAIR_SIZE) {
+        return Err(Error::PrecompileFailed);
+    }
+    let mut points = Vec::new();
+    for pair in input.chunks(PAIR_SIZE) {
+        let a = read_g1(&pair[..64])?;
+        let b = read_g2(&pair[64..])?;
+        if !a.is_zero() && !b.is_zero() {
+            points.push((a, b));
+        }
+    }
+    let success =
+        points.is_empty() || substrate_bn::pairing_batch(&points) == Gt::one();
+    let mut output = vec![0u8; 32];
+    output[31] = success as u8;
+    Ok(output)
+}
+
+fn read_fq(data: &[u8]) -> Result<Fq, Error> {
+    Fq::from_slice(data).map_err(|_| Error::PrecompileFailed)
+}
+
+/// Reads a G1 point encoded as x ++ y, where (0, 0) is the point at infinity.
+fn read_g1(data: &[u8]) -> Result<G1, Error> {
+    let x = read_fq(&data[..32])?;
+    let y = read_fq(&data[32..64])?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(G1::zero());
+    }
+    let point = AffineG1::new(x, y).map_err(|_| Error::PrecompileFailed)?;
+    Ok(point.into())
+}
+
+/// Reads a G2 point encoded as x_im ++ x_re ++ y_im ++ y_re.
+fn read_g2(data: &[u8]) -> Result<G2, Error> {
+    let x = Fq2::new(read_fq(&data[32..64])?, read_fq(&data[..32])?);
+    let y = Fq2::new(read_fq(&data[96..128])?, read_fq(&data[64..96])?);
+    if x.is_zero() && y.is_zero() {
+        return Ok(G2::zero());
+    }
+    let point = AffineG2::new(x, y).map_err(|_| Error::PrecompileFailed)?;
+    Ok(point.into())
+}
+
+fn write_g1(point: G1) -> Vec<u8> {
+    let mut output = vec![0u8; 64];
+    if let Some(point) = AffineG1::from_jacobian(point) {
+        // Both coordinates fit in a word, so writing them cannot fail.
+        point.x().to_big_endian(&mut output[..32]).unwrap();
+        point.y().to_big_endian(&mut output[32..]).unwrap();
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The generator of G1, (1, 2).
+    const G: &str = concat!(
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "0000000000000000000000000000000000000000000000000000000000000002"
+    );
+    // The generator of G1 negated, (1, p - 2).
+    const G_NEG: &str = concat!(
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd45"
+    );
+    // The generator of G2.
+    const H: &str = concat!(
+        "198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2",
+        "1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed",
+        "090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b",
+        "12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa"
+    );
+    const G_DOUBLE: &str = concat!(
+        "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3",
+        "15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4"
+    );
+
+    #[test]
+    fn test_add() {
+        let input = hex::decode([G, G].concat()).unwrap();
+        let mut gas = Gas::new(1000);
+        let output = add(&input, &mut gas, Spec::LATEST).unwrap();
+        assert_eq!(hex::encode(output), G_DOUBLE);
+        assert_eq!(gas.used(), 150);
+        let mut gas = Gas::new(1000);
+        let output = add(&[], &mut gas, Spec::Byzantium).unwrap();
+        assert_eq!(output, vec![0u8; 64]);
+        assert_eq!(gas.used(), 500);
+        // (1, 3) is not on the curve.
+        let mut input = hex::decode(G).unwrap();
+        input[63] = 3;
+        let res = add(&input, &mut gas, Spec::LATEST);
+        assert_eq!(res, Err(Error::PrecompileFailed));
+    }
+
+    #[test]
+    fn test_mul() {
+        let mut input = hex::decode(G).unwrap();
+        input.extend_from_slice(&[0u8; 31]);
+        input.push(2);
+        let mut gas = Gas::new(10000);
+        let output = mul(&input, &mut gas, Spec::LATEST).unwrap();
+        assert_eq!(hex::encode(output), G_DOUBLE);
+        assert_eq!(gas.used(), 6000);
+    }
+
+    #[test]
+    fn test_pairing() {
+        let mut gas = Gas::new(100000);
+        let output = pairing(&[], &mut gas, Spec::LATEST).unwrap();
+        assert_eq!(output[31], 1);
+        assert_eq!(gas.used(), 45000);
+        // e(G, 0) is one, since pairs with the point at infinity are skipped.
+        let mut input = hex::decode(G).unwrap();
+        input.extend_from_slice(&[0u8; 128]);
+        let mut gas = Gas::new(100000);
+        let output = pairing(&input, &mut gas, Spec::LATEST).unwrap();
+        assert_eq!(output[31], 1);
+        assert_eq!(gas.used(), 45000 + 34000);
+        let mut gas = Gas::new(100000);
+        let res = pairing(&input[1..], &mut gas, Spec::LATEST);
+        assert_eq!(res, Err(Error::PrecompileFailed));
+        // e(G, H) * e(-G, H) is one, e(G, H) * e(G, H) is not.
+        let input = hex::decode([G, H, G_NEG, H].concat()).unwrap();
+        let mut gas = Gas::new(1_000_000);
+        let output = pairing(&input, &mut gas, Spec::Byzantium).unwrap();
+        assert_eq!(output[31], 1);
+        assert_eq!(gas.used(), 100000 + 2 * 80000);
+        let input = hex::decode([G, H, G, H].concat()).unwrap();
+        let output = pairing(&input, &mut gas, Spec::LATEST).unwrap();
+        assert_eq!(output[31], 0);
+    }
+}
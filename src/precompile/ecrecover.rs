@@ -0,0 +1,90 @@
+use crate::gas::Gas;
+use crate::precompile::read;
+use crate::spec::Spec;
+use crate::types::Error;
+use ethereum_types::Address;
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use sha3::{Digest, Keccak256};
+
+const ECRECOVER: u64 = 3000;
+
+/// Recovers the signer of hash ++ v ++ r ++ s. Returns the address as a word,
+/// or no output if the signature is invalid.
+pub fn ecrecover(
+    input: &[u8],
+    gas: &mut Gas,
+    _: Spec,
+) -> Result<Vec<u8>, Error> {
+    gas.charge(ECRECOVER)?;
+    let input = read(input, 0, 128);
+    Ok(match recover(&input) {
+        Some(address) => {
+            let mut output = vec![0u8; 12];
+            output.extend_from_slice(address.as_bytes());
+            output
+        }
+        None => Vec::new(),
+    })
+}
+
+fn recover(input: &[u8]) -> Option<Address> {
+    let v = &input[32..64];
+    if v[..31] != [0u8; 31] || !(v[31] == 27 || v[31] == 28) {
+        return None;
+    }
+    let mut recid = RecoveryId::from_byte(v[31] - 27)?;
+    let mut signature = Signature::from_slice(&input[64..128]).ok()?;
+    // Unlike transactions, the precompile accepts signatures with high s.
+    if let Some(normalized) = signature.normalize_s() {
+        signature = normalized;
+        recid = RecoveryId::new(!recid.is_y_odd(), recid.is_x_reduced());
+    }
+    let key =
+        VerifyingKey::recover_from_prehash(&input[..32], &signature, recid)
+            .ok()?;
+    let point = key.to_encoded_point(false);
+    let hash = Keccak256::digest(&point.as_bytes()[1..]);
+    Some(Address::from_slice(&hash[12..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = concat!(
+        "18c547e4f7b0f325ad1e56f57e26c745b09a3e503d86e00e5255ff7f715d3d1c",
+        "000000000000000000000000000000000000000000000000000000000000001c",
+        "73b1693892219d736caba55bdb67216e485557ea6b6af75f37096c9aa6a5a75f",
+        "eeb940b1d03b21e36b0e47e79769f095fe2ab855bd91e3a38756b7d75a9c4549"
+    );
+
+    #[test]
+    fn test_ecrecover() {
+        let input = hex::decode(INPUT).unwrap();
+        let mut gas = Gas::new(5000);
+        let output = ecrecover(&input, &mut gas, Spec::LATEST).unwrap();
+        let expected = concat!(
+            "000000000000000000000000",
+            "a94f5374fce5edbc8e2a8697c15331677e6ebf0b"
+        );
+        assert_eq!(hex::encode(output), expected);
+        assert_eq!(gas.used(), 3000);
+    }
+
+    #[test]
+    fn test_ecrecover_invalid() {
+        let mut input = hex::decode(INPUT).unwrap();
+        input[63] = 29;
+        let mut gas = Gas::new(5000);
+        let output = ecrecover(&input, &mut gas, Spec::LATEST).unwrap();
+        assert!(output.is_empty());
+        // Zero r and s.
+        let mut gas = Gas::new(5000);
+        let input = read(&hex::decode(INPUT).unwrap(), 0, 64);
+        let output = ecrecover(&input, &mut gas, Spec::LATEST);
+        assert_eq!(output, Ok(vec![]));
+        let mut gas = Gas::new(2999);
+        let res = ecrecover(&[], &mut gas, Spec::LATEST);
+        assert_eq!(res, Err(Error::OutOfGas));
+    }
+}
@@ -0,0 +1,81 @@
+use crate::gas::{self, Gas};
+use crate::spec::Spec;
+use crate::types::Error;
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+const SHA256_BASE: u64 = 60;
+const SHA256_WORD: u64 = 12;
+const RIPEMD160_BASE: u64 = 600;
+const RIPEMD160_WORD: u64 = 120;
+const IDENTITY_BASE: u64 = 15;
+const IDENTITY_WORD: u64 = 3;
+
+pub fn sha256(input: &[u8], gas: &mut Gas, _: Spec) -> Result<Vec<u8>, Error> {
+    gas.charge(SHA256_BASE + SHA256_WORD * gas::words(input.len()))?;
+    Ok(Sha256::digest(input).to_vec())
+}
+
+/// Returns the 20-byte hash left-padded to a word.
+pub fn ripemd160(
+    input: &[u8],
+    gas: &mut Gas,
+    _: Spec,
+) -> Result<Vec<u8>, Error> {
+    gas.charge(RIPEMD160_BASE + RIPEMD160_WORD * gas::words(input.len()))?;
+    let mut output = vec![0u8; 12];
+    output.extend_from_slice(&Ripemd160::digest(input));
+    Ok(output)
+}
+
+pub fn identity(
+    input: &[u8],
+    gas: &mut Gas,
+    _: Spec,
+) -> Result<Vec<u8>, Error> {
+    gas.charge(IDENTITY_BASE + IDENTITY_WORD * gas::words(input.len()))?;
+    Ok(input.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256() {
+        let mut gas = Gas::new(1000);
+        let output = sha256(&[], &mut gas, Spec::LATEST).unwrap();
+        let expected = concat!(
+            "e3b0c44298fc1c149afbf4c8996fb924",
+            "27ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(hex::encode(output), expected);
+        assert_eq!(gas.used(), 60);
+        let mut gas = Gas::new(1000);
+        sha256(&[0; 33], &mut gas, Spec::LATEST).unwrap();
+        assert_eq!(gas.used(), 60 + 2 * 12);
+    }
+
+    #[test]
+    fn test_ripemd160() {
+        let mut gas = Gas::new(1000);
+        let output = ripemd160(&[], &mut gas, Spec::LATEST).unwrap();
+        let expected = concat!(
+            "000000000000000000000000",
+            "9c1185a5c5e9fc54612808977ee8f548b2258d31"
+        );
+        assert_eq!(hex::encode(output), expected);
+        assert_eq!(gas.used(), 600);
+        let mut gas = Gas::new(700);
+        let res = ripemd160(&[0; 1], &mut gas, Spec::LATEST);
+        assert_eq!(res, Err(Error::OutOfGas));
+    }
+
+    #[test]
+    fn test_identity() {
+        let mut gas = Gas::new(1000);
+        let output = identity(&[1, 2, 3], &mut gas, Spec::LATEST).unwrap();
+        assert_eq!(output, vec![1, 2, 3]);
+        assert_eq!(gas.used(), 18);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::gas::Gas;
+use crate::spec::Spec;
+use crate::types::Error;
+use c_kzg::{Bytes32, Bytes48};
+use sha2::{Digest, Sha256};
+
+const POINT_EVALUATION: u64 = 50000;
+const INPUT_SIZE: usize = 192;
+const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+
+/// Returns FIELD_ELEMENTS_PER_BLOB ++ BLS_MODULUS, both as words.
+const RETURN_VALUE: [u8; 64] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x73, 0xed, 0xa7, 0x53,
+    0x29, 0x9d, 0x7d, 0x48, 0x33, 0x39, 0xd8, 0x08, 0x09, 0xa1, 0xd8, 0x05,
+    0x53, 0xbd, 0xa4, 0x02, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0xff, 0xff, 0xff,
+    0x00, 0x00, 0x00, 0x01,
+];
+
+/// Verifies a KZG proof that the blob committed to by the versioned hash
+/// evaluates to y at z, see EIP-4844. The input is versioned_hash ++ z ++ y
+/// ++ commitment ++ proof.
+pub fn point_evaluation(
+    input: &[u8],
+    gas: &mut Gas,
+    _: Spec,
+) -> Result<Vec<u8>, Error> {
+    gas.charge(POINT_EVALUATION)?;
+    if input.len() != INPUT_SIZE {
+        return Err(Error::PrecompileFailed);
+    }
+    let commitment = &input[96..144];
+    if input[..32] != versioned_hash(commitment) {
+        return Err(Error::PrecompileFailed);
+    }
+    // The slices have the exact sizes, so the conversions cannot fail.
+    let z = Bytes32::from_bytes(&input[32..64]).unwrap();
+    let y = Bytes32::from_bytes(&input[64..96]).unwrap();
+    let commitment = Bytes48::from_bytes(commitment).unwrap();
+    let proof = Bytes48::from_bytes(&input[144..]).unwrap();
+    let settings = c_kzg::ethereum_kzg_settings(0);
+    match settings.verify_kzg_proof(&commitment, &z, &y, &proof) {
+        Ok(true) => Ok(RETURN_VALUE.to_vec()),
+        _ => Err(Error::PrecompileFailed),
+    }
+}
+
+/// Returns the hash under which a blob commitment is referenced.
+fn versioned_hash(commitment: &[u8]) -> [u8; 32] {
+    let mut hash: [u8; 32] = Sha256::digest(commitment).into();
+    hash[0] = VERSIONED_HASH_VERSION_KZG;
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Proof that the zero polynomial evaluates to zero at zero, for which
+    // the commitment and the proof are both the point at infinity.
+    fn input() -> Vec<u8> {
+        let mut infinity = [0u8; 48];
+        infinity[0] = 0xc0;
+        let mut input = versioned_hash(&infinity).to_vec();
+        input.extend_from_slice(&[0u8; 64]);
+        input.extend_from_slice(&infinity);
+        input.extend_from_slice(&infinity);
+        input
+    }
+
+    #[test]
+    fn test_point_evaluation() {
+        let mut gas = Gas::new(100000);
+        let output = point_evaluation(&input(), &mut gas, Spec::LATEST);
+        assert_eq!(output, Ok(RETURN_VALUE.to_vec()));
+        assert_eq!(gas.used(), 50000);
+    }
+
+    #[test]
+    fn test_point_evaluation_invalid() {
+        let mut gas = Gas::new(1_000_000);
+        // The zero polynomial does not evaluate to one.
+        let mut input = input();
+        input[95] = 1;
+        let res = point_evaluation(&input, &mut gas, Spec::LATEST);
+        assert_eq!(res, Err(Error::PrecompileFailed));
+        input[95] = 0;
+        input[0] = 0x02;
+        let res = point_evaluation(&input, &mut gas, Spec::LATEST);
+        assert_eq!(res, Err(Error::PrecompileFailed));
+        let res = point_evaluation(&input[1..], &mut gas, Spec::LATEST);
+        assert_eq!(res, Err(Error::PrecompileFailed));
+    }
+}
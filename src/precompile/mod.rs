@@ -0,0 +1,205 @@
+mod blake2;
+mod bn128;
+mod ecrecover;
+mod hash;
+mod kzg;
+mod modexp;
+
+use crate::gas::Gas;
+use crate::spec::Spec;
+use crate::types::{Env, Error};
+use ethereum_types::{Address, U256};
+use std::collections::BTreeMap;
+
+/// Signature of the built-in precompiled contracts. A precompile charges its
+/// cost to the given gas and returns its output, or fails with
+/// Error::PrecompileFailed on invalid input.
+pub type PrecompileFn = fn(&[u8], &mut Gas, Spec) -> Result<Vec<u8>, Error>;
+
+/// Returns the built-in precompile at the address if it is active in the fork.
+pub fn get(address: Address, spec: Spec) -> Option<PrecompileFn> {
+    if address[..19] != [0u8; 19] {
+        return None;
+    }
+    let (fork, precompile): (Spec, PrecompileFn) = match address[19] {
+        0x01 => (Spec::Frontier, ecrecover::ecrecover),
+        0x02 => (Spec::Frontier, hash::sha256),
+        0x03 => (Spec::Frontier, hash::ripemd160),
+        0x04 => (Spec::Frontier, hash::identity),
+        0x05 => (Spec::Byzantium, modexp::modexp),
+        0x06 => (Spec::Byzantium, bn128::add),
+        0x07 => (Spec::Byzantium, bn128::mul),
+        0x08 => (Spec::Byzantium, bn128::pairing),
+        0x09 => (Spec::Istanbul, blake2::blake2f),
+        0x0a => (Spec::Cancun, kzg::point_evaluation),
+        _ => return None,
+    };
+    if spec.enabled(fork) {
+        Some(precompile)
+    } else {
+        None
+    }
+}
+
+/// Returns the addresses of the built-in precompiles active in the fork.
+pub fn addresses(spec: Spec) -> impl Iterator<Item = Address> {
+    (0x01..=0x0a)
+        .map(Address::from_low_u64_be)
+        .filter(move |address| get(*address, spec).is_some())
+}
+
+/// A message call into a precompile.
+pub struct PrecompileCall<'a> {
+    pub caller: Address,
+    pub address: Address,
+    pub value: U256,
+    pub input: &'a [u8],
+    pub is_static: bool,
+    pub env: &'a Env,
+    pub spec: Spec,
+}
+
+/// A contract implemented natively, installed at an address of the VM. Like
+/// the built-in ones, a precompile charges its cost to the given gas. It may
+/// fail with Error::Revert to return revert data and keep the unspent gas,
+/// any other error consumes all the gas of the call.
+pub trait Precompile {
+    fn call(
+        &self,
+        call: &PrecompileCall,
+        gas: &mut Gas,
+    ) -> Result<Vec<u8>, Error>;
+}
+
+impl<F> Precompile for F
+where
+    F: Fn(&PrecompileCall, &mut Gas) -> Result<Vec<u8>, Error>,
+{
+    fn call(
+        &self,
+        call: &PrecompileCall,
+        gas: &mut Gas,
+    ) -> Result<Vec<u8>, Error> {
+        self(call, gas)
+    }
+}
+
+/// The precompiles reachable from a VM: the custom ones installed by the
+/// user, then the built-in ones active in the fork.
+#[derive(Default)]
+pub struct Precompiles {
+    custom: BTreeMap<Address, Box<dyn Precompile>>,
+}
+
+impl Precompiles {
+    /// Creates a registry with the built-in precompiles only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Installs the precompile at the address, replacing any built-in one.
+    pub fn insert(
+        &mut self,
+        address: Address,
+        precompile: impl Precompile + 'static,
+    ) {
+        self.custom.insert(address, Box::new(precompile));
+    }
+
+    /// Returns the addresses of all the precompiles reachable in the fork.
+    pub fn addresses(&self, spec: Spec) -> impl Iterator<Item = Address> + '_ {
+        let builtin = addresses(spec)
+            .filter(move |address| !self.custom.contains_key(address));
+        builtin.chain(self.custom.keys().copied())
+    }
+
+    /// Runs the precompile at the called address, or returns None if there
+    /// is no precompile there.
+    pub fn call(
+        &self,
+        call: &PrecompileCall,
+        gas: &mut Gas,
+    ) -> Option<Result<Vec<u8>, Error>> {
+        if let Some(precompile) = self.custom.get(&call.address) {
+            return Some(precompile.call(call, gas));
+        }
+        let precompile = get(call.address, call.spec)?;
+        Some(precompile(call.input, gas, call.spec))
+    }
+}
+
+/// Returns len bytes of the input at the offset, padded with zeros.
+fn read(input: &[u8], offset: usize, len: usize) -> Vec<u8> {
+    let mut data = vec![0u8; len];
+    if offset < input.len() {
+        let end = usize::min(input.len(), offset.saturating_add(len));
+        data[..end - offset].copy_from_slice(&input[offset..end]);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let address = Address::from_low_u64_be;
+        assert!(get(address(0x01), Spec::Frontier).is_some());
+        assert!(get(address(0x05), Spec::SpuriousDragon).is_none());
+        assert!(get(address(0x05), Spec::Byzantium).is_some());
+        assert!(get(address(0x09), Spec::Petersburg).is_none());
+        assert!(get(address(0x09), Spec::Istanbul).is_some());
+        assert!(get(address(0x0a), Spec::Shanghai).is_none());
+        assert!(get(address(0x0a), Spec::Cancun).is_some());
+        assert!(get(address(0x0b), Spec::Cancun).is_none());
+        assert!(get(address(0x00), Spec::Cancun).is_none());
+        assert!(get(Address::repeat_byte(1), Spec::Cancun).is_none());
+        assert_eq!(addresses(Spec::Frontier).count(), 4);
+        assert_eq!(addresses(Spec::Cancun).count(), 10);
+    }
+
+    #[test]
+    fn test_precompiles() {
+        let env = Env::default();
+        let address = Address::from_low_u64_be(0x100);
+        let mut call = PrecompileCall {
+            caller: Address::from_low_u64_be(0xca11),
+            address,
+            value: U256::zero(),
+            input: &[1, 2, 3],
+            is_static: false,
+            env: &env,
+            spec: Spec::LATEST,
+        };
+        let mut precompiles = Precompiles::new();
+        let mut gas = Gas::new(100);
+        assert!(precompiles.call(&call, &mut gas).is_none());
+        precompiles.insert(address, |call: &PrecompileCall, gas: &mut Gas| {
+            gas.charge(10)?;
+            Ok(call.caller.as_bytes().to_vec())
+        });
+        let output = precompiles.call(&call, &mut gas).unwrap();
+        assert_eq!(output, Ok(call.caller.as_bytes().to_vec()));
+        assert_eq!(gas.used(), 10);
+        assert_eq!(precompiles.addresses(Spec::Cancun).count(), 11);
+        // Built-in precompiles can be replaced.
+        call.address = Address::from_low_u64_be(0x04);
+        let output = precompiles.call(&call, &mut gas).unwrap();
+        assert_eq!(output, Ok(vec![1, 2, 3]));
+        precompiles.insert(call.address, |_: &PrecompileCall, _: &mut Gas| {
+            Err(Error::Revert(vec![0xff]))
+        });
+        let output = precompiles.call(&call, &mut gas).unwrap();
+        assert_eq!(output, Err(Error::Revert(vec![0xff])));
+        assert_eq!(precompiles.addresses(Spec::Cancun).count(), 11);
+        assert_eq!(precompiles.addresses(Spec::Frontier).count(), 5);
+    }
+
+    #[test]
+    fn test_read() {
+        assert_eq!(read(&[1, 2, 3], 1, 4), vec![2, 3, 0, 0]);
+        assert_eq!(read(&[1, 2, 3], 5, 2), vec![0, 0]);
+        assert_eq!(read(&[1, 2, 3], 0, 2), vec![1, 2]);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::gas::Gas;
+use crate::precompile::read;
+use crate::spec::Spec;
+use crate::types::Error;
+use ethereum_types::U256;
+use num_bigint::BigUint;
+
+const MIN_GAS_BERLIN: u64 = 200;
+
+/// Computes base ** exp % mod for operands of arbitrary length, see EIP-198.
+/// The input starts with the lengths of the three operands as words.
+pub fn modexp(
+    input: &[u8],
+    gas: &mut Gas,
+    spec: Spec,
+) -> Result<Vec<u8>, Error> {
+    let base_len = U256::from_big_endian(&read(input, 0, 32));
+    let exp_len = U256::from_big_endian(&read(input, 32, 32));
+    let mod_len = U256::from_big_endian(&read(input, 64, 32));
+    // Lengths this large cost more than any gas limit.
+    let max = U256::from(u32::MAX);
+    if base_len > max || exp_len > max || mod_len > max {
+        gas.charge(u64::MAX)?;
+        return Err(Error::OutOfGas);
+    }
+    let base_len = base_len.as_usize();
+    let exp_len = exp_len.as_usize();
+    let mod_len = mod_len.as_usize();
+    let exp_head_len = usize::min(exp_len, 32);
+    let exp_head = read(input, 96 + base_len, exp_head_len);
+    let exp_head = U256::from_big_endian(&exp_head);
+    gas.charge(cost(spec, base_len, exp_len, mod_len, exp_head))?;
+    if mod_len == 0 {
+        return Ok(Vec::new());
+    }
+    let base = BigUint::from_bytes_be(&read(input, 96, base_len));
+    let exp = read(input, 96 + base_len, exp_len);
+    let exp = BigUint::from_bytes_be(&exp);
+    let modulus = read(input, 96 + base_len + exp_len, mod_len);
+    let modulus = BigUint::from_bytes_be(&modulus);
+    let result = if modulus == BigUint::ZERO {
+        Vec::new()
+    } else {
+        base.modpow(&exp, &modulus).to_bytes_be()
+    };
+    let mut output = vec![0u8; mod_len - result.len()];
+    output.extend_from_slice(&result);
+    Ok(output)
+}
+
+/// Returns the cost of the operation, which was repriced in Berlin, see
+/// EIP-2565.
+fn cost(
+    spec: Spec,
+    base_len: usize,
+    exp_len: usize,
+    mod_len: usize,
+    exp_head: U256,
+) -> u64 {
+    let max_len = usize::max(base_len, mod_len) as u128;
+    let iterations = u128::max(iterations(exp_len, exp_head), 1);
+    let cost = if spec.enabled(Spec::Berlin) {
+        let words = max_len.div_ceil(8);
+        let cost = words * words * iterations / 3;
+        u128::max(cost, MIN_GAS_BERLIN as u128)
+    } else {
+        let complexity = if max_len <= 64 {
+            max_len * max_len
+        } else if max_len <= 1024 {
+            max_len * max_len / 4 + 96 * max_len - 3072
+        } else {
+            max_len * max_len / 16 + 480 * max_len - 199680
+        };
+        complexity * iterations / 20
+    };
+    u64::try_from(cost).unwrap_or(u64::MAX)
+}
+
+/// Returns the adjusted length of the exponent, based on its length and the
+/// position of the highest bit in its first word.
+fn iterations(exp_len: usize, exp_head: U256) -> u128 {
+    let head_bits = exp_head.bits().saturating_sub(1) as u128;
+    if exp_len <= 32 {
+        head_bits
+    } else {
+        8 * (exp_len as u128 - 32) + head_bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(base: &[u8], exp: &[u8], modulus: &[u8]) -> Vec<u8> {
+        let mut input = Vec::new();
+        for operand in [base, exp, modulus] {
+            let mut len = [0u8; 32];
+            U256::from(operand.len()).to_big_endian(&mut len);
+            input.extend_from_slice(&len);
+        }
+        [input, base.to_vec(), exp.to_vec(), modulus.to_vec()].concat()
+    }
+
+    #[test]
+    fn test_modexp() {
+        let mut gas = Gas::new(1000);
+        let output =
+            modexp(&input(&[3], &[5], &[0, 100]), &mut gas, Spec::LATEST);
+        assert_eq!(output, Ok(vec![0, 43]));
+        assert_eq!(gas.used(), 200);
+        let mut gas = Gas::new(1000);
+        let output = modexp(&input(&[3], &[5], &[]), &mut gas, Spec::LATEST);
+        assert_eq!(output, Ok(vec![]));
+        let output = modexp(&input(&[3], &[5], &[0]), &mut gas, Spec::LATEST);
+        assert_eq!(output, Ok(vec![0]));
+        let output = modexp(&input(&[3], &[], &[7]), &mut gas, Spec::LATEST);
+        assert_eq!(output, Ok(vec![1]));
+        // Operands missing from the input are zero.
+        let output =
+            modexp(&input(&[3], &[], &[7])[..97], &mut gas, Spec::LATEST);
+        assert_eq!(output, Ok(vec![0]));
+    }
+
+    #[test]
+    fn test_modexp_cost() {
+        let head = U256::from;
+        assert_eq!(cost(Spec::Byzantium, 1, 1, 2, head(5)), 4 * 2 / 20);
+        assert_eq!(cost(Spec::Byzantium, 64, 32, 64, head(0)), 4096 / 20);
+        assert_eq!(cost(Spec::Byzantium, 100, 1, 1, head(1)), 9028 / 20);
+        assert_eq!(cost(Spec::Berlin, 64, 32, 64, U256::MAX), 64 * 255 / 3);
+        assert_eq!(cost(Spec::Berlin, 1, 33, 1, head(1)), 200);
+        assert_eq!(cost(Spec::Berlin, 1, 40, 1, head(2)), 200);
+        assert_eq!(iterations(40, head(2)), 8 * 8 + 1);
+        let mut gas = Gas::new(u64::MAX - 1);
+        let mut input = vec![0xff; 32];
+        input.extend_from_slice(&[0; 64]);
+        assert_eq!(
+            modexp(&input, &mut gas, Spec::LATEST),
+            Err(Error::OutOfGas)
+        );
+    }
+}
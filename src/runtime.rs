@@ -1,60 +1,168 @@
+use crate::analysis::JumpTable;
 use crate::db::Database;
+use crate::gas::{self, Gas};
 use crate::i256;
 use crate::mem::Mem;
+use crate::precompile::{PrecompileCall, Precompiles};
+use crate::spec::Spec;
 use crate::stack::Stack;
-use crate::state::State;
-use crate::types::{Env, Error, Log, OpResult, OpStep, RunResult};
+use crate::state::{Checkpoint, State};
+use crate::types::{
+    code_hash, Account, Env, Error, ExecResult, Log, OpResult, OpStep,
+    RunResult,
+};
 use core::cmp::Ordering;
-use ethereum_types::{H256, U256, U512};
+use ethereum_types::{Address, H256, U256, U512};
 use sha3::{Digest, Keccak256};
+use std::rc::Rc;
+
+const MAX_CALL_DEPTH: usize = 1024;
+const MAX_CODE_SIZE: usize = 24576;
+const MAX_INITCODE_SIZE: usize = 2 * MAX_CODE_SIZE;
+const BLOCK_HASH_WINDOW: u64 = 256;
 
 struct Context<'a, DB> {
+    address: Address,
+    caller: Address,
     code: &'a [u8],
+    jumps: Rc<JumpTable>,
+    calldata: &'a [u8],
+    value: U256,
     state: &'a mut State<DB>,
     env: &'a Env,
+    precompiles: &'a Precompiles,
+    depth: usize,
+    is_static: bool,
+    spec: Spec,
     pc: usize,
     mem: Mem,
     stack: Stack,
     logs: Vec<Log>,
+    returndata: Vec<u8>,
+    gas: Gas,
+}
+
+/// Returns the extra gas charged for a cold account or storage access since
+/// Berlin, on top of the warm access cost. See EIP-2929.
+fn cold_cost(spec: Spec, cold: bool, cost: u64) -> u64 {
+    if cold && spec.enabled(Spec::Berlin) {
+        cost - gas::WARM_STORAGE_READ
+    } else {
+        0
+    }
+}
+
+/// Charges the given cost to access an account, plus the cold surcharge.
+fn charge_access<DB: Database>(
+    ctx: &mut Context<DB>,
+    address: Address,
+    cost: u64,
+) -> Result<(), Error> {
+    let cold = ctx.state.access_account(address);
+    let extra = cold_cost(ctx.spec, cold, gas::COLD_ACCOUNT_ACCESS);
+    ctx.gas.charge(cost + extra)
+}
+
+/// Charges memory expansion gas and resizes the memory to fit the given area.
+fn expand_mem<DB>(
+    ctx: &mut Context<DB>,
+    start: usize,
+    len: usize,
+) -> Result<(), Error> {
+    ctx.gas.charge(ctx.mem.expansion_cost(start, len)?)?;
+    if len > 0 {
+        ctx.mem.resize_for(start + len)?;
+    }
+    Ok(())
+}
+
+/// Converts a memory offset taken from the stack. The offset of an empty
+/// area is never used, so it may be any value.
+fn mem_offset(offset: U256, len: usize) -> Result<usize, Error> {
+    if len == 0 {
+        Ok(0)
+    } else if offset <= usize::MAX.into() {
+        Ok(offset.as_usize())
+    } else {
+        Err(Error::StackValueOutOfRange)
+    }
 }
 
-fn handle_0x00_stop<DB>(_ctx: &mut Context<DB>) -> OpResult {
+/// Pops the offset and length of a memory area from the stack.
+fn pop_area<DB>(ctx: &mut Context<DB>) -> Result<(usize, usize), Error> {
+    let start = ctx.stack.pop_u256()?;
+    let len = ctx.stack.pop_usize()?;
+    Ok((mem_offset(start, len)?, len))
+}
+
+/// Returns len bytes of data starting at offset, zero-padded past the end.
+fn padded_slice(data: &[u8], offset: U256, len: usize) -> Vec<u8> {
+    let mut out = vec![0u8; len];
+    if offset < U256::from(data.len()) {
+        let start = offset.as_usize();
+        let end = usize::min(data.len(), start.saturating_add(len));
+        out[..end - start].copy_from_slice(&data[start..end]);
+    }
+    out
+}
+
+/// Copies the area described on the stack from the given data into memory.
+fn copy_to_mem<DB>(ctx: &mut Context<DB>, data: &[u8]) -> OpResult {
+    let dst = ctx.stack.pop_u256()?;
+    let offset = ctx.stack.pop_u256()?;
+    let len = ctx.stack.pop_usize()?;
+    let dst = mem_offset(dst, len)?;
+    ctx.gas.charge(gas::VERYLOW + gas::COPY * gas::words(len))?;
+    expand_mem(ctx, dst, len)?;
+    ctx.mem.mwrite(dst, &padded_slice(data, offset, len))?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x00_stop<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::ZERO)?;
     Ok(OpStep::Return(Vec::new()))
 }
 
 fn handle_0x01_add<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
-    ctx.stack.push_u256(lhs + rhs)?;
+    ctx.stack.push_u256(lhs.overflowing_add(rhs).0)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x02_mul<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::LOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
-    ctx.stack.push_u256(lhs * rhs)?;
+    ctx.stack.push_u256(lhs.overflowing_mul(rhs).0)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x03_sub<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
-    ctx.stack.push_u256(lhs - rhs)?;
+    ctx.stack.push_u256(lhs.overflowing_sub(rhs).0)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x04_div<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::LOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
-    ctx.stack.push_u256(lhs / rhs)?;
+    let res = lhs.checked_div(rhs).unwrap_or(U256::zero());
+    ctx.stack.push_u256(res)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x05_sdiv<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::LOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
     ctx.stack.push_u256(i256::i256_div(lhs, rhs))?;
@@ -63,15 +171,17 @@ fn handle_0x05_sdiv<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x06_mod<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::LOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
-    let res = lhs.checked_rem(rhs).unwrap_or(0.into());
+    let res = lhs.checked_rem(rhs).unwrap_or(U256::zero());
     ctx.stack.push_u256(res)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x07_smod<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::LOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
     ctx.stack.push_u256(i256::i256_mod(lhs, rhs))?;
@@ -80,6 +190,7 @@ fn handle_0x07_smod<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x08_addmod<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::MID)?;
     let lhs: U512 = ctx.stack.pop_u256()?.into();
     let rhs: U512 = ctx.stack.pop_u256()?.into();
     let base: U512 = ctx.stack.pop_u256()?.into();
@@ -90,10 +201,11 @@ fn handle_0x08_addmod<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x09_mulmod<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::MID)?;
     let lhs: U512 = ctx.stack.pop_u256()?.into();
     let rhs: U512 = ctx.stack.pop_u256()?.into();
     let base: U512 = ctx.stack.pop_u256()?.into();
-    let res = (lhs + rhs).checked_rem(base).unwrap_or(U512::zero());
+    let res = (lhs * rhs).checked_rem(base).unwrap_or(U512::zero());
     ctx.stack.push_u256(res.try_into().unwrap())?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
@@ -102,12 +214,25 @@ fn handle_0x09_mulmod<DB>(ctx: &mut Context<DB>) -> OpResult {
 fn handle_0x0a_exp<DB>(ctx: &mut Context<DB>) -> OpResult {
     let base = ctx.stack.pop_u256()?;
     let exp = ctx.stack.pop_u256()?;
+    let exp_bytes = (exp.bits() as u64).div_ceil(8);
+    ctx.gas
+        .charge(gas::EXP + ctx.spec.schedule().expbyte * exp_bytes)?;
     ctx.stack.push_u256(base.overflowing_pow(exp).0)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
+fn handle_0x0b_signextend<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::LOW)?;
+    let ext = ctx.stack.pop_u256()?;
+    let value = ctx.stack.pop_u256()?;
+    ctx.stack.push_u256(i256::i256_signextend(ext, value))?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
 fn handle_0x10_lt<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
     ctx.stack.push_usize(if lhs < rhs { 1 } else { 0 })?;
@@ -116,6 +241,7 @@ fn handle_0x10_lt<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x11_gt<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
     ctx.stack.push_usize(if lhs > rhs { 1 } else { 0 })?;
@@ -124,6 +250,7 @@ fn handle_0x11_gt<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x12_slt<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
     let islt = i256::i256_cmp(lhs, rhs) == Ordering::Less;
@@ -133,6 +260,7 @@ fn handle_0x12_slt<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x13_sgt<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
     let isgt = i256::i256_cmp(lhs, rhs) == Ordering::Greater;
@@ -142,6 +270,7 @@ fn handle_0x13_sgt<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x14_eq<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
     ctx.stack.push_usize(if lhs == rhs { 1 } else { 0 })?;
@@ -150,6 +279,7 @@ fn handle_0x14_eq<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x15_iszero<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let value = ctx.stack.pop_u256()?;
     ctx.stack.push_usize(if value.is_zero() { 1 } else { 0 })?;
     ctx.pc += 1;
@@ -157,6 +287,7 @@ fn handle_0x15_iszero<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x16_and<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
     ctx.stack.push_u256(lhs & rhs)?;
@@ -165,6 +296,7 @@ fn handle_0x16_and<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x17_or<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
     ctx.stack.push_u256(lhs | rhs)?;
@@ -173,6 +305,7 @@ fn handle_0x17_or<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x18_xor<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let lhs = ctx.stack.pop_u256()?;
     let rhs = ctx.stack.pop_u256()?;
     ctx.stack.push_u256(lhs ^ rhs)?;
@@ -181,159 +314,515 @@ fn handle_0x18_xor<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x19_not<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let value = ctx.stack.pop_u256()?;
     ctx.stack.push_u256(!value)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
+fn handle_0x1a_byte<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
+    let index = ctx.stack.pop_u256()?;
+    let value = ctx.stack.pop_u256()?;
+    let res = if index < U256::from(32) {
+        value.byte(31 - index.as_usize())
+    } else {
+        0
+    };
+    ctx.stack.push_usize(res as usize)?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
 fn handle_0x1b_shl<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let shift = ctx.stack.pop_u256()?;
     let value = ctx.stack.pop_u256()?;
-    ctx.stack.push_u256(value << shift)?;
+    let res = if shift < U256::from(256) {
+        value << shift.as_usize()
+    } else {
+        U256::zero()
+    };
+    ctx.stack.push_u256(res)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x1c_shr<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let shift = ctx.stack.pop_u256()?;
     let value = ctx.stack.pop_u256()?;
-    ctx.stack.push_u256(value >> shift)?;
+    let res = if shift < U256::from(256) {
+        value >> shift.as_usize()
+    } else {
+        U256::zero()
+    };
+    ctx.stack.push_u256(res)?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x1d_sar<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
+    let shift = ctx.stack.pop_u256()?;
+    let value = ctx.stack.pop_u256()?;
+    ctx.stack.push_u256(i256::i256_sar(shift, value))?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x20_keccak256<DB>(ctx: &mut Context<DB>) -> OpResult {
-    let start = ctx.stack.pop_usize()?;
-    let len = ctx.stack.pop_usize()?;
+    let (start, len) = pop_area(ctx)?;
+    ctx.gas
+        .charge(gas::KECCAK256 + gas::KECCAK256WORD * gas::words(len))?;
+    expand_mem(ctx, start, len)?;
     let res = Keccak256::digest(ctx.mem.mview(start, len)?);
     ctx.stack.push_h256(H256::from_slice(&res))?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
+fn handle_0x30_address<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_h256(ctx.address.into())?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x31_balance<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    let address = Address::from(ctx.stack.pop_h256()?);
+    charge_access(ctx, address, ctx.spec.schedule().balance)?;
+    ctx.stack
+        .push_u256(ctx.state.load_account(address)?.balance)?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x32_origin<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_h256(ctx.env.tx.caller.into())?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
 fn handle_0x33_caller<DB>(ctx: &mut Context<DB>) -> OpResult {
-    ctx.stack.push_h256(ctx.env.caller.into())?;
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_h256(ctx.caller.into())?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x34_callvalue<DB>(ctx: &mut Context<DB>) -> OpResult {
-    ctx.stack.push_usize(0)?;
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_u256(ctx.value)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x35_calldataload<DB>(ctx: &mut Context<DB>) -> OpResult {
-    let loc = ctx.stack.pop_usize()?;
-    let mut rawdata = [0u8; 32];
-    for idx in 0..(usize::min(32, ctx.env.calldata.len() - loc)) {
-        rawdata[idx] = ctx.env.calldata[loc + idx];
-    }
+    ctx.gas.charge(gas::VERYLOW)?;
+    let loc = ctx.stack.pop_u256()?;
+    let rawdata = padded_slice(ctx.calldata, loc, 32);
     ctx.stack.push_u256(U256::from_big_endian(&rawdata))?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x36_calldatasize<DB>(ctx: &mut Context<DB>) -> OpResult {
-    ctx.stack.push_usize(ctx.env.calldata.len())?;
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_usize(ctx.calldata.len())?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x37_calldatacopy<DB>(ctx: &mut Context<DB>) -> OpResult {
+    let calldata = ctx.calldata;
+    copy_to_mem(ctx, calldata)
+}
+
+fn handle_0x38_codesize<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_usize(ctx.code.len())?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x39_codecopy<DB>(ctx: &mut Context<DB>) -> OpResult {
+    let code = ctx.code;
+    copy_to_mem(ctx, code)
+}
+
+fn handle_0x3a_gasprice<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_u256(ctx.env.tx.gasprice)?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x3b_extcodesize<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    let address = Address::from(ctx.stack.pop_h256()?);
+    charge_access(ctx, address, ctx.spec.schedule().extcode)?;
+    ctx.stack.push_usize(ctx.state.load_code(address)?.len())?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x3d_returndatasize<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_usize(ctx.returndata.len())?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x3e_returndatacopy<DB>(ctx: &mut Context<DB>) -> OpResult {
+    let dst = ctx.stack.pop_u256()?;
+    let offset = ctx.stack.pop_u256()?;
+    let len = ctx.stack.pop_usize()?;
+    let dst = mem_offset(dst, len)?;
+    ctx.gas.charge(gas::VERYLOW + gas::COPY * gas::words(len))?;
+    if offset.saturating_add(len.into()) > ctx.returndata.len().into() {
+        return Err(Error::ReturnDataOutOfBound);
+    }
+    expand_mem(ctx, dst, len)?;
+    let start = offset.as_usize();
+    ctx.mem.mwrite(dst, &ctx.returndata[start..start + len])?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x3f_extcodehash<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    let address = Address::from(ctx.stack.pop_h256()?);
+    charge_access(ctx, address, ctx.spec.schedule().extcodehash)?;
+    let account = ctx.state.load_account(address)?;
+    // Accounts that do not exist, or are empty, hash to zero, see EIP-1052.
+    if account.is_empty() {
+        ctx.stack.push_h256(H256::zero())?;
+    } else {
+        ctx.stack.push_h256(account.code_hash)?;
+    }
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x40_blockhash<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BLOCKHASH)?;
+    let number = ctx.stack.pop_u256()?;
+    let current = ctx.env.block.number;
+    let hash = if number < current
+        && current - number <= BLOCK_HASH_WINDOW.into()
+        && number <= u64::MAX.into()
+    {
+        ctx.state.block_hash(number.as_u64())?
+    } else {
+        H256::zero()
+    };
+    ctx.stack.push_h256(hash)?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x41_coinbase<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_h256(ctx.env.block.coinbase.into())?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x42_timestamp<DB>(ctx: &mut Context<DB>) -> OpResult {
-    ctx.stack.push_u256(ctx.env.timestamp)?;
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_u256(ctx.env.block.timestamp)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x43_number<DB>(ctx: &mut Context<DB>) -> OpResult {
-    ctx.stack.push_u256(ctx.env.number)?;
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_u256(ctx.env.block.number)?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x44_prevrandao<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
+    if ctx.spec.enabled(Spec::Merge) {
+        ctx.stack.push_h256(ctx.env.block.prevrandao)?;
+    } else {
+        ctx.stack.push_u256(ctx.env.block.difficulty)?;
+    }
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x45_gaslimit<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_u256(ctx.env.block.gaslimit)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x46_chainid<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
     ctx.stack.push_u256(ctx.env.chainid)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
+fn handle_0x47_selfbalance<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::LOW)?;
+    let balance = ctx.state.load_account(ctx.address)?.balance;
+    ctx.stack.push_u256(balance)?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x48_basefee<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_u256(ctx.env.block.basefee)?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x49_blobhash<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
+    let index = ctx.stack.pop_u256()?;
+    let hashes = &ctx.env.tx.blob_hashes;
+    let hash = if index < hashes.len().into() {
+        hashes[index.as_usize()]
+    } else {
+        H256::zero()
+    };
+    ctx.stack.push_h256(hash)?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x4a_blobbasefee<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_u256(ctx.env.block.blob_basefee)?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
 fn handle_0x50_pop<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
     ctx.stack.pop()?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x51_mload<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let key = ctx.stack.pop_usize()?;
+    expand_mem(ctx, key, 32)?;
     ctx.stack.push_u256(ctx.mem.mload(key)?)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x52_mstore<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let key = ctx.stack.pop_usize()?;
     let value = ctx.stack.pop_u256()?;
+    expand_mem(ctx, key, 32)?;
     ctx.mem.mstore(key, value)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x53_mstores<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     let key = ctx.stack.pop_usize()?;
     let value = ctx.stack.pop_u256()?;
-    ctx.mem.mstores(key, value.byte(31))?;
+    expand_mem(ctx, key, 1)?;
+    ctx.mem.mstores(key, value.byte(0))?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x54_sload<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
     let key = ctx.stack.pop_u256()?;
-    ctx.stack.push_u256(ctx.state.load(key))?;
+    let cold = ctx.state.access_slot(ctx.address, key);
+    let extra = cold_cost(ctx.spec, cold, gas::COLD_SLOAD);
+    ctx.gas.charge(ctx.spec.schedule().sload + extra)?;
+    ctx.stack.push_u256(ctx.state.load(ctx.address, key)?)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
+/// Charges SSTORE gas and updates the refund based on the value of the slot
+/// at the start of the transaction, see EIP-2200 and EIP-3529.
+fn sstore_net_metering<DB>(
+    ctx: &mut Context<DB>,
+    original: U256,
+    current: U256,
+    value: U256,
+) -> Result<(), Error> {
+    let schedule = ctx.spec.schedule();
+    let sclear_refund = schedule.sclear_refund as i64;
+    if current == value {
+        return ctx.gas.charge(schedule.sload);
+    }
+    if original == current {
+        if original.is_zero() {
+            return ctx.gas.charge(gas::SSET);
+        }
+        if value.is_zero() {
+            ctx.gas.refund(sclear_refund);
+        }
+        return ctx.gas.charge(schedule.sreset);
+    }
+    if !original.is_zero() {
+        if current.is_zero() {
+            ctx.gas.refund(-sclear_refund);
+        } else if value.is_zero() {
+            ctx.gas.refund(sclear_refund);
+        }
+    }
+    if original == value {
+        let restored = if original.is_zero() {
+            gas::SSET
+        } else {
+            schedule.sreset
+        };
+        ctx.gas.refund((restored - schedule.sload) as i64);
+    }
+    ctx.gas.charge(schedule.sload)
+}
+
 fn handle_0x55_sstore<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    if ctx.is_static {
+        return Err(Error::StaticCallViolation);
+    }
     let key = ctx.stack.pop_u256()?;
     let value = ctx.stack.pop_u256()?;
-    ctx.state.store(key, value);
+    if ctx.spec.enabled(Spec::Istanbul)
+        && ctx.gas.remaining() <= gas::CALLSTIPEND
+    {
+        return Err(Error::OutOfGas);
+    }
+    let cold = ctx.state.access_slot(ctx.address, key);
+    if cold && ctx.spec.enabled(Spec::Berlin) {
+        ctx.gas.charge(gas::COLD_SLOAD)?;
+    }
+    let current = ctx.state.load(ctx.address, key)?;
+    if ctx.spec.enabled(Spec::Istanbul) {
+        let original = ctx.state.original(ctx.address, key)?;
+        sstore_net_metering(ctx, original, current, value)?;
+    } else {
+        let schedule = ctx.spec.schedule();
+        if current.is_zero() && !value.is_zero() {
+            ctx.gas.charge(gas::SSET)?;
+        } else {
+            ctx.gas.charge(schedule.sreset)?;
+        }
+        if !current.is_zero() && value.is_zero() {
+            ctx.gas.refund(schedule.sclear_refund as i64);
+        }
+    }
+    ctx.state.store(ctx.address, key, value);
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
+/// Returns the given jump target if it is a valid JUMPDEST in the code.
+fn jump_target<DB>(ctx: &Context<DB>, to: U256) -> Result<usize, Error> {
+    if to < U256::from(ctx.code.len()) && ctx.jumps.is_valid(to.as_usize()) {
+        Ok(to.as_usize())
+    } else {
+        Err(Error::InvalidJump { from: ctx.pc, to })
+    }
+}
+
 fn handle_0x56_jump<DB>(ctx: &mut Context<DB>) -> OpResult {
-    let loc = ctx.stack.pop_usize()?;
-    ctx.pc = loc;
+    ctx.gas.charge(gas::MID)?;
+    let loc = ctx.stack.pop_u256()?;
+    ctx.pc = jump_target(ctx, loc)?;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x57_jumpi<DB>(ctx: &mut Context<DB>) -> OpResult {
-    let loc = ctx.stack.pop_usize()?;
+    ctx.gas.charge(gas::HIGH)?;
+    let loc = ctx.stack.pop_u256()?;
     let cond = ctx.stack.pop_u256()?;
-    ctx.pc = if cond.is_zero() { ctx.pc + 1 } else { loc };
+    ctx.pc = if cond.is_zero() {
+        ctx.pc + 1
+    } else {
+        jump_target(ctx, loc)?
+    };
     Ok(OpStep::Continue)
 }
 
 fn handle_0x58_pc<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
     ctx.stack.push_usize(ctx.pc)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x59_msize<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
     ctx.stack.push_usize(ctx.mem.size())?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
+fn handle_0x5a_gas<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_u256(ctx.gas.remaining().into())?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
 fn handle_0x5b_jumpdest<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::JUMPDEST)?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x5c_tload<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::WARM_STORAGE_READ)?;
+    let key = ctx.stack.pop_u256()?;
+    ctx.stack.push_u256(ctx.state.tload(ctx.address, key))?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x5d_tstore<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    if ctx.is_static {
+        return Err(Error::StaticCallViolation);
+    }
+    ctx.gas.charge(gas::WARM_STORAGE_READ)?;
+    let key = ctx.stack.pop_u256()?;
+    let value = ctx.stack.pop_u256()?;
+    ctx.state.tstore(ctx.address, key, value);
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x5e_mcopy<DB>(ctx: &mut Context<DB>) -> OpResult {
+    let dst = ctx.stack.pop_u256()?;
+    let src = ctx.stack.pop_u256()?;
+    let len = ctx.stack.pop_usize()?;
+    let dst = mem_offset(dst, len)?;
+    let src = mem_offset(src, len)?;
+    ctx.gas.charge(gas::VERYLOW + gas::COPY * gas::words(len))?;
+    expand_mem(ctx, src, len)?;
+    expand_mem(ctx, dst, len)?;
+    ctx.mem.mcopy(dst, src, len)?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0x5f_push0<DB>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::BASE)?;
+    ctx.stack.push_usize(0)?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x60_push<DB, const N: usize>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     if N < ctx.code.len() - ctx.pc {
         let slice = &ctx.code[ctx.pc + 1..ctx.pc + N + 1];
         let value = U256::from_big_endian(slice);
@@ -346,20 +835,27 @@ fn handle_0x60_push<DB, const N: usize>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0x80_dup<DB, const N: usize>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     ctx.stack.dup::<N>()?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0x90_swap<DB, const N: usize>(ctx: &mut Context<DB>) -> OpResult {
+    ctx.gas.charge(gas::VERYLOW)?;
     ctx.stack.swap::<N>()?;
     ctx.pc += 1;
     Ok(OpStep::Continue)
 }
 
 fn handle_0xa0_log0<DB>(ctx: &mut Context<DB>) -> OpResult {
-    let start = ctx.stack.pop_usize()?;
-    let len = ctx.stack.pop_usize()?;
+    if ctx.is_static {
+        return Err(Error::StaticCallViolation);
+    }
+    let (start, len) = pop_area(ctx)?;
+    let cost = gas::log_cost(0, len)?;
+    ctx.gas.charge(cost)?;
+    expand_mem(ctx, start, len)?;
     ctx.logs.push(Log {
         topics: vec![],
         data: ctx.mem.mview(start, len)?.into(),
@@ -369,8 +865,13 @@ fn handle_0xa0_log0<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0xa1_log1<DB>(ctx: &mut Context<DB>) -> OpResult {
-    let start = ctx.stack.pop_usize()?;
-    let len = ctx.stack.pop_usize()?;
+    if ctx.is_static {
+        return Err(Error::StaticCallViolation);
+    }
+    let (start, len) = pop_area(ctx)?;
+    let cost = gas::log_cost(1, len)?;
+    ctx.gas.charge(cost)?;
+    expand_mem(ctx, start, len)?;
     let topic0 = ctx.stack.pop_h256()?;
     ctx.logs.push(Log {
         topics: vec![topic0],
@@ -381,8 +882,13 @@ fn handle_0xa1_log1<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0xa2_log2<DB>(ctx: &mut Context<DB>) -> OpResult {
-    let start = ctx.stack.pop_usize()?;
-    let len = ctx.stack.pop_usize()?;
+    if ctx.is_static {
+        return Err(Error::StaticCallViolation);
+    }
+    let (start, len) = pop_area(ctx)?;
+    let cost = gas::log_cost(2, len)?;
+    ctx.gas.charge(cost)?;
+    expand_mem(ctx, start, len)?;
     let topic0 = ctx.stack.pop_h256()?;
     let topic1 = ctx.stack.pop_h256()?;
     ctx.logs.push(Log {
@@ -394,8 +900,13 @@ fn handle_0xa2_log2<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0xa3_log3<DB>(ctx: &mut Context<DB>) -> OpResult {
-    let start = ctx.stack.pop_usize()?;
-    let len = ctx.stack.pop_usize()?;
+    if ctx.is_static {
+        return Err(Error::StaticCallViolation);
+    }
+    let (start, len) = pop_area(ctx)?;
+    let cost = gas::log_cost(3, len)?;
+    ctx.gas.charge(cost)?;
+    expand_mem(ctx, start, len)?;
     let topic0 = ctx.stack.pop_h256()?;
     let topic1 = ctx.stack.pop_h256()?;
     let topic2 = ctx.stack.pop_h256()?;
@@ -408,8 +919,13 @@ fn handle_0xa3_log3<DB>(ctx: &mut Context<DB>) -> OpResult {
 }
 
 fn handle_0xa4_log4<DB>(ctx: &mut Context<DB>) -> OpResult {
-    let start = ctx.stack.pop_usize()?;
-    let len = ctx.stack.pop_usize()?;
+    if ctx.is_static {
+        return Err(Error::StaticCallViolation);
+    }
+    let (start, len) = pop_area(ctx)?;
+    let cost = gas::log_cost(4, len)?;
+    ctx.gas.charge(cost)?;
+    expand_mem(ctx, start, len)?;
     let topic0 = ctx.stack.pop_h256()?;
     let topic1 = ctx.stack.pop_h256()?;
     let topic2 = ctx.stack.pop_h256()?;
@@ -422,20 +938,437 @@ fn handle_0xa4_log4<DB>(ctx: &mut Context<DB>) -> OpResult {
     Ok(OpStep::Continue)
 }
 
+/// Returns the address of a contract created by the sender with the nonce.
+pub fn create_address(sender: Address, nonce: u64) -> Address {
+    let mut stream = rlp::RlpStream::new_list(2);
+    stream.append(&sender);
+    stream.append(&nonce);
+    Address::from_slice(&Keccak256::digest(stream.out())[12..])
+}
+
+/// Returns the address of a contract created by CREATE2 with the salt.
+pub fn create2_address(
+    sender: Address,
+    salt: H256,
+    initcode: &[u8],
+) -> Address {
+    let mut hasher = Keccak256::new();
+    hasher.update([0xff]);
+    hasher.update(sender.as_bytes());
+    hasher.update(salt.as_bytes());
+    hasher.update(Keccak256::digest(initcode));
+    Address::from_slice(&hasher.finalize()[12..])
+}
+
+/// Returns the most gas a child frame may receive, see EIP-150.
+fn max_child_gas<DB>(ctx: &Context<DB>) -> u64 {
+    if ctx.spec.enabled(Spec::TangerineWhistle) {
+        ctx.gas.remaining() - ctx.gas.remaining() / 64
+    } else {
+        ctx.gas.remaining()
+    }
+}
+
+/// Merges the outcome of a child frame into the parent. State changes made by
+/// the child are reverted unless it succeeded.
+fn settle_child<DB: Database>(
+    ctx: &mut Context<DB>,
+    res: ExecResult,
+    child_gas: Gas,
+    checkpoint: Checkpoint,
+) -> Result<(bool, Vec<u8>), Error> {
+    match res {
+        Ok((output, logs)) => {
+            ctx.state.discard(checkpoint);
+            ctx.logs.extend(logs);
+            ctx.gas.refund(child_gas.refunded());
+            ctx.gas.return_gas(child_gas.remaining());
+            Ok((true, output))
+        }
+        Err(Error::Revert(output)) => {
+            ctx.state.revert_to(checkpoint);
+            ctx.gas.return_gas(child_gas.remaining());
+            Ok((false, output))
+        }
+        // A failing database aborts the whole transaction, not just the frame.
+        Err(err @ Error::Database(_)) => {
+            ctx.state.revert_to(checkpoint);
+            Err(err)
+        }
+        Err(_) => {
+            ctx.state.revert_to(checkpoint);
+            Ok((false, Vec::new()))
+        }
+    }
+}
+
+/// Checks the code returned by init code, charges for it and stores it to the
+/// account at the specified address.
+fn deposit_code<DB: Database>(
+    state: &mut State<DB>,
+    spec: Spec,
+    address: Address,
+    code: &[u8],
+    gas: &mut Gas,
+) -> Result<(), Error> {
+    if spec.enabled(Spec::SpuriousDragon) && code.len() > MAX_CODE_SIZE {
+        return Err(Error::CodeSizeExceeded);
+    }
+    let cost = gas::CODEDEPOSIT * code.len() as u64;
+    if !spec.enabled(Spec::Homestead) && cost > gas.remaining() {
+        // Frontier keeps the contract without code instead of failing.
+        return Ok(());
+    }
+    gas.charge(cost)?;
+    state.store_code(address, code.to_vec())?;
+    Ok(())
+}
+
+/// Runs the init code of a new contract in a new frame and deploys the code
+/// it returns. Pushes the address of the new contract, or zero on failure.
+fn contract_create<DB: Database>(
+    ctx: &mut Context<DB>,
+    initcode: Vec<u8>,
+    address: Address,
+    value: U256,
+) -> OpResult {
+    ctx.returndata.clear();
+    let balance = ctx.state.load_account(ctx.address)?.balance;
+    if ctx.depth + 1 > MAX_CALL_DEPTH || balance < value {
+        ctx.stack.push_usize(0)?;
+        ctx.pc += 1;
+        return Ok(OpStep::Continue);
+    }
+    let mut sender = ctx.state.load_account(ctx.address)?;
+    sender.nonce += 1;
+    ctx.state.store_account(ctx.address, sender);
+    ctx.state.access_account(address);
+    let gas_limit = max_child_gas(ctx);
+    ctx.gas.charge(gas_limit)?;
+    let target = ctx.state.load_account(address)?;
+    if target.nonce != 0 || target.has_code() {
+        ctx.stack.push_usize(0)?;
+        ctx.pc += 1;
+        return Ok(OpStep::Continue);
+    }
+    let checkpoint = ctx.state.checkpoint();
+    ctx.state
+        .store_account(address, Account { nonce: 1, ..target });
+    ctx.state.transfer(ctx.address, address, value)?;
+    // Init code runs once, its analysis is not worth caching.
+    let jumps = Rc::new(JumpTable::analyze(&initcode));
+    let (mut res, mut child_gas) = execute(Context {
+        address,
+        caller: ctx.address,
+        code: &initcode,
+        jumps,
+        calldata: &[],
+        value,
+        state: ctx.state,
+        env: ctx.env,
+        precompiles: ctx.precompiles,
+        depth: ctx.depth + 1,
+        is_static: false,
+        spec: ctx.spec,
+        pc: 0,
+        mem: Mem::new(),
+        stack: Stack::new(),
+        logs: Vec::new(),
+        returndata: Vec::new(),
+        gas: Gas::new(gas_limit),
+    });
+    if let Ok((code, _)) = &res {
+        if let Err(err) =
+            deposit_code(ctx.state, ctx.spec, address, code, &mut child_gas)
+        {
+            res = Err(err);
+        }
+    }
+    let (success, output) = settle_child(ctx, res, child_gas, checkpoint)?;
+    if success {
+        ctx.stack.push_h256(address.into())?;
+    } else {
+        ctx.returndata = output;
+        ctx.stack.push_usize(0)?;
+    }
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+/// Charges for and returns the init code in the specified memory area.
+fn read_initcode<DB>(
+    ctx: &mut Context<DB>,
+    start: usize,
+    len: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut cost = gas::CREATE;
+    if ctx.spec.enabled(Spec::Shanghai) {
+        if len > MAX_INITCODE_SIZE {
+            return Err(Error::InitcodeSizeExceeded);
+        }
+        cost += gas::INITCODEWORD * gas::words(len);
+    }
+    ctx.gas.charge(cost)?;
+    expand_mem(ctx, start, len)?;
+    Ok(ctx.mem.mview(start, len)?.to_vec())
+}
+
+fn handle_0xf0_create<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    if ctx.is_static {
+        return Err(Error::StaticCallViolation);
+    }
+    let value = ctx.stack.pop_u256()?;
+    let (start, len) = pop_area(ctx)?;
+    let initcode = read_initcode(ctx, start, len)?;
+    let nonce = ctx.state.load_account(ctx.address)?.nonce;
+    let address = create_address(ctx.address, nonce);
+    contract_create(ctx, initcode, address, value)
+}
+
+/// Parameters of a message call into a new frame.
+struct Message {
+    gas: U256,
+    address: Address,
+    caller: Address,
+    code_address: Address,
+    value: U256,
+    transfer: bool,
+    is_static: bool,
+}
+
+/// Runs a message call in a new frame and pushes the success flag.
+fn message_call<DB: Database>(ctx: &mut Context<DB>, msg: Message) -> OpResult {
+    let (args_start, args_len) = pop_area(ctx)?;
+    let (ret_start, ret_len) = pop_area(ctx)?;
+    charge_access(ctx, msg.code_address, ctx.spec.schedule().call)?;
+    let transfers_value = msg.transfer && !msg.value.is_zero();
+    if transfers_value {
+        ctx.gas.charge(gas::CALLVALUE)?;
+    }
+    // Before EIP-161 any call may create the account, not only value transfers.
+    let creates_account = if ctx.spec.enabled(Spec::SpuriousDragon) {
+        transfers_value
+    } else {
+        msg.transfer
+    };
+    if creates_account
+        && msg.address == msg.code_address
+        && ctx.state.load_account(msg.address)?.is_empty()
+    {
+        ctx.gas.charge(gas::NEWACCOUNT)?;
+    }
+    expand_mem(ctx, args_start, args_len)?;
+    expand_mem(ctx, ret_start, ret_len)?;
+    let requested = if msg.gas > u64::MAX.into() {
+        u64::MAX
+    } else {
+        msg.gas.as_u64()
+    };
+    let mut gas_limit = if ctx.spec.enabled(Spec::TangerineWhistle) {
+        u64::min(requested, max_child_gas(ctx))
+    } else {
+        requested
+    };
+    ctx.gas.charge(gas_limit)?;
+    if transfers_value {
+        gas_limit += gas::CALLSTIPEND;
+    }
+    ctx.returndata.clear();
+    let balance = ctx.state.load_account(ctx.address)?.balance;
+    if ctx.depth + 1 > MAX_CALL_DEPTH || (msg.transfer && balance < msg.value) {
+        ctx.gas.return_gas(gas_limit);
+        ctx.stack.push_usize(0)?;
+        ctx.pc += 1;
+        return Ok(OpStep::Continue);
+    }
+    let calldata = ctx.mem.mview(args_start, args_len)?.to_vec();
+    let checkpoint = ctx.state.checkpoint();
+    if msg.transfer {
+        ctx.state.transfer(ctx.address, msg.address, msg.value)?;
+    }
+    let call = PrecompileCall {
+        caller: msg.caller,
+        address: msg.code_address,
+        value: msg.value,
+        input: &calldata,
+        is_static: msg.is_static,
+        env: ctx.env,
+        spec: ctx.spec,
+    };
+    if let Some((res, child_gas)) =
+        run_precompile(ctx.precompiles, &call, gas_limit)
+    {
+        return finish_call(
+            ctx, res, child_gas, checkpoint, ret_start, ret_len,
+        );
+    }
+    let (hash, code) = ctx.state.load_code_hashed(msg.code_address)?;
+    // A call to an account without code only transfers the value.
+    if code.is_empty() {
+        let res = Ok((vec![], vec![]));
+        return finish_call(
+            ctx,
+            res,
+            Gas::new(gas_limit),
+            checkpoint,
+            ret_start,
+            ret_len,
+        );
+    }
+    let jumps = ctx.state.jump_table(hash, &code);
+    let (res, child_gas) = execute(Context {
+        address: msg.address,
+        caller: msg.caller,
+        code: &code,
+        jumps,
+        calldata: &calldata,
+        value: msg.value,
+        state: ctx.state,
+        env: ctx.env,
+        precompiles: ctx.precompiles,
+        depth: ctx.depth + 1,
+        is_static: msg.is_static,
+        spec: ctx.spec,
+        pc: 0,
+        mem: Mem::new(),
+        stack: Stack::new(),
+        logs: Vec::new(),
+        returndata: Vec::new(),
+        gas: Gas::new(gas_limit),
+    });
+    finish_call(ctx, res, child_gas, checkpoint, ret_start, ret_len)
+}
+
+/// Runs the precompile at the called address as a frame of its own, which
+/// returns no logs. Returns None if there is no precompile there.
+fn run_precompile(
+    precompiles: &Precompiles,
+    call: &PrecompileCall,
+    gas_limit: u64,
+) -> Option<(ExecResult, Gas)> {
+    let mut gas = Gas::new(gas_limit);
+    let res = precompiles.call(call, &mut gas)?;
+    Some((res.map(|output| (output, vec![])), gas))
+}
+
+/// Settles a finished message call, copies its output to the return area
+/// and pushes the success flag.
+fn finish_call<DB: Database>(
+    ctx: &mut Context<DB>,
+    res: ExecResult,
+    child_gas: Gas,
+    checkpoint: Checkpoint,
+    ret_start: usize,
+    ret_len: usize,
+) -> OpResult {
+    let (success, output) = settle_child(ctx, res, child_gas, checkpoint)?;
+    let len = usize::min(ret_len, output.len());
+    ctx.mem.mwrite(ret_start, &output[..len])?;
+    ctx.returndata = output;
+    ctx.stack.push_usize(if success { 1 } else { 0 })?;
+    ctx.pc += 1;
+    Ok(OpStep::Continue)
+}
+
+fn handle_0xf1_call<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    let gas = ctx.stack.pop_u256()?;
+    let address = Address::from(ctx.stack.pop_h256()?);
+    let value = ctx.stack.pop_u256()?;
+    if ctx.is_static && !value.is_zero() {
+        return Err(Error::StaticCallViolation);
+    }
+    let msg = Message {
+        gas,
+        address,
+        caller: ctx.address,
+        code_address: address,
+        value,
+        transfer: true,
+        is_static: ctx.is_static,
+    };
+    message_call(ctx, msg)
+}
+
+fn handle_0xf2_callcode<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    let gas = ctx.stack.pop_u256()?;
+    let code_address = Address::from(ctx.stack.pop_h256()?);
+    let value = ctx.stack.pop_u256()?;
+    let msg = Message {
+        gas,
+        address: ctx.address,
+        caller: ctx.address,
+        code_address,
+        value,
+        transfer: true,
+        is_static: ctx.is_static,
+    };
+    message_call(ctx, msg)
+}
+
 fn handle_0xf3_return<DB>(ctx: &mut Context<DB>) -> OpResult {
-    let start = ctx.stack.pop_usize()?;
-    let len = ctx.stack.pop_usize()?;
+    ctx.gas.charge(gas::ZERO)?;
+    let (start, len) = pop_area(ctx)?;
+    expand_mem(ctx, start, len)?;
     Ok(OpStep::Return(ctx.mem.mview(start, len)?.to_vec()))
 }
 
+fn handle_0xf4_delegatecall<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    let gas = ctx.stack.pop_u256()?;
+    let code_address = Address::from(ctx.stack.pop_h256()?);
+    let msg = Message {
+        gas,
+        address: ctx.address,
+        caller: ctx.caller,
+        code_address,
+        value: ctx.value,
+        transfer: false,
+        is_static: ctx.is_static,
+    };
+    message_call(ctx, msg)
+}
+
+fn handle_0xf5_create2<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    if ctx.is_static {
+        return Err(Error::StaticCallViolation);
+    }
+    let value = ctx.stack.pop_u256()?;
+    let (start, len) = pop_area(ctx)?;
+    let salt = ctx.stack.pop_h256()?;
+    let initcode = read_initcode(ctx, start, len)?;
+    ctx.gas
+        .charge(gas::KECCAK256WORD * gas::words(initcode.len()))?;
+    let address = create2_address(ctx.address, salt, &initcode);
+    contract_create(ctx, initcode, address, value)
+}
+
+fn handle_0xfa_staticcall<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
+    let gas = ctx.stack.pop_u256()?;
+    let address = Address::from(ctx.stack.pop_h256()?);
+    let msg = Message {
+        gas,
+        address,
+        caller: ctx.address,
+        code_address: address,
+        value: U256::zero(),
+        transfer: false,
+        is_static: true,
+    };
+    message_call(ctx, msg)
+}
+
 fn handle_0xfd_revert<DB>(ctx: &mut Context<DB>) -> OpResult {
-    let start = ctx.stack.pop_usize()?;
-    let len = ctx.stack.pop_usize()?;
+    ctx.gas.charge(gas::ZERO)?;
+    let (start, len) = pop_area(ctx)?;
+    expand_mem(ctx, start, len)?;
     Err(Error::Revert(ctx.mem.mview(start, len)?.to_vec()))
 }
 
 fn next<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
-    match ctx.code[ctx.pc] {
+    let opcode = ctx.code[ctx.pc];
+    if !ctx.spec.has_opcode(opcode) {
+        return Err(Error::InvalidOpcode(opcode));
+    }
+    match opcode {
         0x00 => handle_0x00_stop(ctx),
         0x01 => handle_0x01_add(ctx),
         0x02 => handle_0x02_mul(ctx),
@@ -447,7 +1380,7 @@ fn next<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
         0x08 => handle_0x08_addmod(ctx),
         0x09 => handle_0x09_mulmod(ctx),
         0x0a => handle_0x0a_exp(ctx),
-        // 0x0b => handle_0x0b_signextended(ctx),
+        0x0b => handle_0x0b_signextend(ctx),
         0x10 => handle_0x10_lt(ctx),
         0x11 => handle_0x11_gt(ctx),
         0x12 => handle_0x12_slt(ctx),
@@ -458,19 +1391,37 @@ fn next<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
         0x17 => handle_0x17_or(ctx),
         0x18 => handle_0x18_xor(ctx),
         0x19 => handle_0x19_not(ctx),
-        // 0x1a => handle_0x1a_byte(ctx),
+        0x1a => handle_0x1a_byte(ctx),
         0x1b => handle_0x1b_shl(ctx),
         0x1c => handle_0x1c_shr(ctx),
-        // 0x1d => handle_0x1c_sar(ctx),
+        0x1d => handle_0x1d_sar(ctx),
         0x20 => handle_0x20_keccak256(ctx),
+        0x30 => handle_0x30_address(ctx),
+        0x31 => handle_0x31_balance(ctx),
+        0x32 => handle_0x32_origin(ctx),
         0x33 => handle_0x33_caller(ctx),
         0x34 => handle_0x34_callvalue(ctx),
         0x35 => handle_0x35_calldataload(ctx),
         0x36 => handle_0x36_calldatasize(ctx),
-        // 0x37 => handle_0x36_calldatacopy(ctx),
+        0x37 => handle_0x37_calldatacopy(ctx),
+        0x38 => handle_0x38_codesize(ctx),
+        0x39 => handle_0x39_codecopy(ctx),
+        0x3a => handle_0x3a_gasprice(ctx),
+        0x3b => handle_0x3b_extcodesize(ctx),
+        0x3d => handle_0x3d_returndatasize(ctx),
+        0x3e => handle_0x3e_returndatacopy(ctx),
+        0x3f => handle_0x3f_extcodehash(ctx),
+        0x40 => handle_0x40_blockhash(ctx),
+        0x41 => handle_0x41_coinbase(ctx),
         0x42 => handle_0x42_timestamp(ctx),
         0x43 => handle_0x43_number(ctx),
+        0x44 => handle_0x44_prevrandao(ctx),
+        0x45 => handle_0x45_gaslimit(ctx),
         0x46 => handle_0x46_chainid(ctx),
+        0x47 => handle_0x47_selfbalance(ctx),
+        0x48 => handle_0x48_basefee(ctx),
+        0x49 => handle_0x49_blobhash(ctx),
+        0x4a => handle_0x4a_blobbasefee(ctx),
         0x50 => handle_0x50_pop(ctx),
         0x51 => handle_0x51_mload(ctx),
         0x52 => handle_0x52_mstore(ctx),
@@ -481,7 +1432,12 @@ fn next<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
         0x57 => handle_0x57_jumpi(ctx),
         0x58 => handle_0x58_pc(ctx),
         0x59 => handle_0x59_msize(ctx),
+        0x5a => handle_0x5a_gas(ctx),
         0x5b => handle_0x5b_jumpdest(ctx),
+        0x5c => handle_0x5c_tload(ctx),
+        0x5d => handle_0x5d_tstore(ctx),
+        0x5e => handle_0x5e_mcopy(ctx),
+        0x5f => handle_0x5f_push0(ctx),
         0x60 => handle_0x60_push::<_, 1>(ctx),
         0x61 => handle_0x60_push::<_, 2>(ctx),
         0x62 => handle_0x60_push::<_, 3>(ctx),
@@ -551,34 +1507,1370 @@ fn next<DB: Database>(ctx: &mut Context<DB>) -> OpResult {
         0xa2 => handle_0xa2_log2(ctx),
         0xa3 => handle_0xa3_log3(ctx),
         0xa4 => handle_0xa4_log4(ctx),
+        0xf0 => handle_0xf0_create(ctx),
+        0xf1 => handle_0xf1_call(ctx),
+        0xf2 => handle_0xf2_callcode(ctx),
         0xf3 => handle_0xf3_return(ctx),
+        0xf4 => handle_0xf4_delegatecall(ctx),
+        0xf5 => handle_0xf5_create2(ctx),
+        0xfa => handle_0xfa_staticcall(ctx),
         0xfd => handle_0xfd_revert(ctx),
         opcode => Err(Error::InvalidOpcode(opcode)),
     }
 }
 
-pub fn run<'a, 'b, DB: Database>(
-    code: &'a [u8],
+/// Executes the frame until it halts and returns its result and gas meter.
+fn execute<DB: Database>(mut ctx: Context<DB>) -> (ExecResult, Gas) {
+    let result = loop {
+        // Running past the end of the code is an implicit STOP.
+        if ctx.pc >= ctx.code.len() {
+            break Ok((vec![], ctx.logs));
+        }
+        match next(&mut ctx) {
+            Err(err) => break Err(err),
+            Ok(OpStep::Continue) => (),
+            Ok(OpStep::Return(v)) => break Ok((v, ctx.logs)),
+        }
+    };
+    (result, ctx.gas)
+}
+
+pub fn run<'b, DB: Database>(
+    address: Address,
+    code: &[u8],
     state: &'b mut State<DB>,
     env: &'b Env,
+    precompiles: &'b Precompiles,
+    spec: Spec,
+    gas_limit: u64,
 ) -> RunResult {
-    let mut ctx = Context {
+    warm_accounts(state, env, precompiles, spec, address);
+    let call = PrecompileCall {
+        caller: env.tx.caller,
+        address,
+        value: env.tx.value,
+        input: &env.tx.calldata,
+        is_static: false,
+        env,
+        spec,
+    };
+    if let Some((result, gas)) = run_precompile(precompiles, &call, gas_limit) {
+        return finish(result, gas, gas_limit);
+    }
+    // A transaction to an account without code is a plain value transfer.
+    if code.is_empty() {
+        return finish(Ok((vec![], vec![])), Gas::new(gas_limit), gas_limit);
+    }
+    let jumps = state.jump_table(code_hash(code), code);
+    let (result, gas) = execute(Context {
+        address,
+        caller: env.tx.caller,
         code,
+        jumps,
+        calldata: &env.tx.calldata,
+        value: env.tx.value,
         state,
         env,
+        precompiles,
+        depth: 0,
+        is_static: false,
+        spec,
         pc: 0,
         mem: Mem::new(),
         stack: Stack::new(),
         logs: Vec::new(),
+        returndata: Vec::new(),
+        gas: Gas::new(gas_limit),
+    });
+    finish(result, gas, gas_limit)
+}
+
+/// Runs the init code as a new contract at the given address and stores the
+/// returned code to the account.
+pub fn deploy<'b, DB: Database>(
+    address: Address,
+    initcode: &[u8],
+    state: &'b mut State<DB>,
+    env: &'b Env,
+    precompiles: &'b Precompiles,
+    spec: Spec,
+    gas_limit: u64,
+) -> RunResult {
+    if spec.enabled(Spec::Shanghai) && initcode.len() > MAX_INITCODE_SIZE {
+        let err = Error::InitcodeSizeExceeded;
+        return finish(Err(err), Gas::new(0), gas_limit);
+    }
+    let target = match state.load_account(address) {
+        Ok(target) => target,
+        Err(err) => return finish(Err(err.into()), Gas::new(0), gas_limit),
     };
-    loop {
-        if ctx.pc >= ctx.code.len() {
-            return Err(Error::CodeOutOfBound);
+    if target.nonce != 0 || target.has_code() {
+        return finish(Err(Error::CreateCollision), Gas::new(0), gas_limit);
+    }
+    warm_accounts(state, env, precompiles, spec, address);
+    state.store_account(address, Account { nonce: 1, ..target });
+    let jumps = Rc::new(JumpTable::analyze(initcode));
+    let (mut result, mut gas) = execute(Context {
+        address,
+        caller: env.tx.caller,
+        code: initcode,
+        jumps,
+        calldata: &[],
+        value: env.tx.value,
+        state,
+        env,
+        precompiles,
+        depth: 0,
+        is_static: false,
+        spec,
+        pc: 0,
+        mem: Mem::new(),
+        stack: Stack::new(),
+        logs: Vec::new(),
+        returndata: Vec::new(),
+        gas: Gas::new(gas_limit),
+    });
+    if let Ok((code, _)) = &result {
+        if let Err(err) = deposit_code(state, spec, address, code, &mut gas) {
+            result = Err(err);
         }
-        match next(&mut ctx) {
-            Err(err) => return Err(err),
-            Ok(OpStep::Continue) => (),
-            Ok(OpStep::Return(v)) => return Ok((v, ctx.logs)),
+    }
+    finish(result, gas, gas_limit)
+}
+
+/// Marks the accounts that are warm from the start of a transaction.
+fn warm_accounts<DB: Database>(
+    state: &mut State<DB>,
+    env: &Env,
+    precompiles: &Precompiles,
+    spec: Spec,
+    address: Address,
+) {
+    state.access_account(env.tx.caller);
+    state.access_account(address);
+    for precompile in precompiles.addresses(spec) {
+        state.access_account(precompile);
+    }
+    if spec.enabled(Spec::Shanghai) {
+        // See EIP-3651.
+        state.access_account(env.block.coinbase);
+    }
+}
+
+/// Reports the gas spent by the top-level frame alongside its result.
+fn finish(result: ExecResult, gas: Gas, gas_limit: u64) -> RunResult {
+    let (gas_used, gas_refund) = match result {
+        Ok(_) => (gas.used(), u64::try_from(gas.refunded()).unwrap_or(0)),
+        Err(Error::Revert(_)) => (gas.used(), 0),
+        Err(_) => (gas_limit, 0),
+    };
+    RunResult {
+        result,
+        gas_used,
+        gas_refund,
+        gas_left: gas_limit - gas_used,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{DbError, DbIter, MemoryDB, WriteOp};
+    use crate::types::Account;
+    use crate::types::{BlockEnv, TxEnv, KECCAK_EMPTY};
+
+    const SPEC: Spec = Spec::LATEST;
+    const CALLEE: &str = "00000000000000000000000000000000000000aa";
+
+    fn test_env() -> Env {
+        Env {
+            chainid: 1.into(),
+            block: BlockEnv::default(),
+            tx: TxEnv {
+                gaslimit: 1_000_000,
+                ..TxEnv::default()
+            },
+        }
+    }
+
+    fn exec_in(state: &mut State<MemoryDB>, code: &str, gas: u64) -> RunResult {
+        let code = hex::decode(code).unwrap();
+        run(
+            Address::zero(),
+            &code,
+            state,
+            &test_env(),
+            &Precompiles::new(),
+            SPEC,
+            gas,
+        )
+    }
+
+    fn exec(code: &str, gas_limit: u64) -> RunResult {
+        exec_in(&mut State::new(MemoryDB::new()), code, gas_limit)
+    }
+
+    fn exec_with(spec: Spec, code: &str, gas_limit: u64) -> RunResult {
+        let code = hex::decode(code).unwrap();
+        let mut state = State::new(MemoryDB::new());
+        run(
+            Address::zero(),
+            &code,
+            &mut state,
+            &test_env(),
+            &Precompiles::new(),
+            spec,
+            gas_limit,
+        )
+    }
+
+    /// Returns a state with the given code deployed at the callee address.
+    fn state_with_callee(code: &str) -> State<MemoryDB> {
+        let mut state = State::new(MemoryDB::new());
+        let code = hex::decode(code).unwrap();
+        state.store_code(CALLEE.parse().unwrap(), code).unwrap();
+        state
+    }
+
+    #[test]
+    fn test_gas_static() {
+        // PUSH1 1, PUSH1 2, ADD, STOP
+        let res = exec("600160020100", 100);
+        assert_eq!(res.result, Ok((vec![], vec![])));
+        assert_eq!(res.gas_used, 9);
+        assert_eq!(res.gas_left, 91);
+    }
+
+    #[test]
+    fn test_gas_infinite_loop() {
+        // JUMPDEST, PUSH1 0, JUMP
+        let res = exec("5b600056", 10000);
+        assert_eq!(res.result, Err(Error::OutOfGas));
+        assert_eq!(res.gas_used, 10000);
+        assert_eq!(res.gas_left, 0);
+    }
+
+    #[test]
+    fn test_gas_log_huge_length() {
+        // PUSH8 2^62, PUSH1 0, LOG0
+        let res = exec("6740000000000000006000a0", 100000);
+        assert_eq!(res.result, Err(Error::OutOfGas));
+        // PUSH1 0 (x4), PUSH8 2^63, PUSH1 0, LOG4
+        let res = exec("60006000600060006780000000000000006000a4", 100000);
+        assert_eq!(res.result, Err(Error::OutOfGas));
+    }
+
+    #[test]
+    fn test_gas_memory_expansion() {
+        // PUSH1 1, PUSH1 64, MSTORE, STOP
+        let res = exec("600160405200", 100);
+        assert_eq!(res.gas_used, 3 + 3 + 3 + 9);
+    }
+
+    #[test]
+    fn test_gas_sstore_refund() {
+        // PUSH1 1, PUSH1 0, SSTORE, PUSH1 0, PUSH1 0, SSTORE, STOP
+        let res = exec_with(Spec::Frontier, "6001600055600060005500", 100000);
+        assert_eq!(res.gas_used, 3 + 3 + 20000 + 3 + 3 + 5000);
+        assert_eq!(res.gas_refund, 15000);
+    }
+
+    #[test]
+    fn test_gas_sstore_net_metering() {
+        // PUSH1 1, PUSH1 0, SSTORE, PUSH1 0, PUSH1 0, SSTORE, STOP
+        let code = "6001600055600060005500";
+        let res = exec_with(Spec::Istanbul, code, 100000);
+        assert_eq!(res.gas_used, 3 + 3 + 20000 + 3 + 3 + 800);
+        assert_eq!(res.gas_refund, 20000 - 800);
+        let res = exec_with(Spec::Cancun, code, 100000);
+        assert_eq!(res.gas_used, 3 + 3 + 2100 + 20000 + 3 + 3 + 100);
+        assert_eq!(res.gas_refund, 20000 - 100);
+        // PUSH1 1, PUSH1 0, SSTORE, STOP with only the stipend left
+        let res = exec_with(Spec::Cancun, "600160005500", 6 + 2300);
+        assert_eq!(res.result, Err(Error::OutOfGas));
+    }
+
+    #[test]
+    fn test_gas_sstore_existing_slot() {
+        let mut state = State::new(MemoryDB::new());
+        state.store(Address::zero(), 0.into(), 1.into());
+        state.commit().unwrap();
+        // SSTORE(0, 0), SSTORE(0, 1), STOP: clear and restore the slot.
+        let code = hex::decode("6000600055600160005500").unwrap();
+        let env = test_env();
+        let res = run(
+            Address::zero(),
+            &code,
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            SPEC,
+            100000,
+        );
+        assert_eq!(res.gas_used, 3 + 3 + 2100 + 2900 + 3 + 3 + 100);
+        // The clearing refund is taken back, only the restore refund stays.
+        assert_eq!(res.gas_refund, 2900 - 100);
+    }
+
+    #[test]
+    fn test_gas_cold_access() {
+        // SLOAD(0), SLOAD(0), BALANCE(0xaa), BALANCE(0xaa), STOP
+        let code = "60005460005460aa3160aa3100";
+        let res = exec_with(Spec::Berlin, code, 100000);
+        assert_eq!(res.gas_used, 3 + 2100 + 3 + 100 + 3 + 2600 + 3 + 100);
+        let res = exec_with(Spec::Istanbul, code, 100000);
+        assert_eq!(res.gas_used, 3 + 800 + 3 + 800 + 3 + 700 + 3 + 700);
+        let res = exec_with(Spec::Frontier, code, 100000);
+        assert_eq!(res.gas_used, 3 + 50 + 3 + 50 + 3 + 20 + 3 + 20);
+    }
+
+    #[test]
+    fn test_opcode_availability() {
+        // PUSH0, STOP
+        let res = exec_with(Spec::Merge, "5f00", 100000);
+        assert_eq!(res.result, Err(Error::InvalidOpcode(0x5f)));
+        let res = exec_with(Spec::Shanghai, "5f00", 100000);
+        assert_eq!(res.result, Ok((vec![], vec![])));
+        assert_eq!(res.gas_used, 2);
+        // PUSH1 1, PUSH1 1, SHL, STOP
+        let res = exec_with(Spec::Byzantium, "600160011b00", 100000);
+        assert_eq!(res.result, Err(Error::InvalidOpcode(0x1b)));
+        let res = exec_with(Spec::Constantinople, "600160011b00", 100000);
+        assert_eq!(res.result, Ok((vec![], vec![])));
+        // PUSH1 0, PUSH1 0, REVERT
+        let res = exec_with(Spec::SpuriousDragon, "60006000fd", 100000);
+        assert_eq!(res.result, Err(Error::InvalidOpcode(0xfd)));
+    }
+
+    #[test]
+    fn test_transient_storage() {
+        // TSTORE(0, 42), TLOAD(0), return the word
+        let code = "602a60005d60005c60005260206000f3";
+        let res = exec_with(Spec::Cancun, code, 100000);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, word(42));
+        let res = exec_with(Spec::Shanghai, code, 100000);
+        assert_eq!(res.result, Err(Error::InvalidOpcode(0x5d)));
+    }
+
+    #[test]
+    fn test_storage_per_address() {
+        // PUSH1 42, PUSH1 0, SSTORE, STOP
+        let code = hex::decode("602a60005500").unwrap();
+        let env = test_env();
+        let addr1 = Address::from_low_u64_be(0x101);
+        let addr2 = Address::from_low_u64_be(0x102);
+        let mut state = State::new(MemoryDB::new());
+        let res = run(
+            addr1,
+            &code,
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            SPEC,
+            100000,
+        );
+        assert_eq!(res.result, Ok((vec![], vec![])));
+        assert_eq!(state.load(addr1, 0.into()).unwrap(), 42.into());
+        assert_eq!(state.load(addr2, 0.into()).unwrap(), 0.into());
+    }
+
+    #[test]
+    fn test_gas_revert_keeps_remaining() {
+        // PUSH1 0, PUSH1 0, REVERT
+        let res = exec("60006000fd", 100);
+        assert_eq!(res.result, Err(Error::Revert(vec![])));
+        assert_eq!(res.gas_used, 6);
+        assert_eq!(res.gas_left, 94);
+    }
+
+    // Stores 42 at slot 0 and returns the 32-byte word 7.
+    const STORE_AND_RETURN: &str = "602a600055600760005260206000f3";
+    // Stores 42 at slot 0 and reverts with empty data.
+    const STORE_AND_REVERT: &str = "602a60005560006000fd";
+
+    /// Returns code that invokes the callee with the given call opcode and
+    /// returns the 32-byte output followed by the success flag.
+    fn caller_code(opcode: &str) -> String {
+        let value = if opcode == "f1" || opcode == "f2" {
+            "6000"
+        } else {
+            ""
+        };
+        format!(
+            "6020600060006000{}60aa61ffff{}60205260406000f3",
+            value, opcode
+        )
+    }
+
+    fn word(value: u64) -> Vec<u8> {
+        let mut data = [0u8; 32];
+        U256::from(value).to_big_endian(&mut data);
+        data.to_vec()
+    }
+
+    #[test]
+    fn test_call() {
+        let mut state = state_with_callee(STORE_AND_RETURN);
+        let res = exec_in(&mut state, &caller_code("f1"), 100000);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, [word(7), word(1)].concat());
+        let callee = CALLEE.parse().unwrap();
+        assert_eq!(state.load(callee, 0.into()).unwrap(), 42.into());
+        assert_eq!(state.load(Address::zero(), 0.into()).unwrap(), 0.into());
+    }
+
+    #[test]
+    fn test_call_revert() {
+        let mut state = state_with_callee(STORE_AND_REVERT);
+        let res = exec_in(&mut state, &caller_code("f1"), 100000);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, [word(0), word(0)].concat());
+        let callee = CALLEE.parse().unwrap();
+        assert_eq!(state.load(callee, 0.into()).unwrap(), 0.into());
+    }
+
+    #[test]
+    fn test_call_out_of_gas_consumes_forwarded_gas() {
+        let mut state = state_with_callee("5b600056");
+        let res = exec_in(&mut state, &caller_code("f1"), 100000);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, [word(0), word(0)].concat());
+        assert!(res.gas_used > 0xffff);
+    }
+
+    #[test]
+    fn test_staticcall() {
+        let mut state = state_with_callee(STORE_AND_RETURN);
+        let res = exec_in(&mut state, &caller_code("fa"), 100000);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, [word(0), word(0)].concat());
+        let callee = CALLEE.parse().unwrap();
+        assert_eq!(state.load(callee, 0.into()).unwrap(), 0.into());
+
+        // PUSH1 7, PUSH1 0, MSTORE, PUSH1 32, PUSH1 0, RETURN
+        let mut state = state_with_callee("600760005260206000f3");
+        let res = exec_in(&mut state, &caller_code("fa"), 100000);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, [word(7), word(1)].concat());
+    }
+
+    #[test]
+    fn test_delegatecall_and_callcode() {
+        for opcode in ["f4", "f2"] {
+            let mut state = state_with_callee(STORE_AND_RETURN);
+            let res = exec_in(&mut state, &caller_code(opcode), 100000);
+            let (output, _) = res.result.unwrap();
+            assert_eq!(output, [word(7), word(1)].concat());
+            let callee = CALLEE.parse().unwrap();
+            assert_eq!(state.load(callee, 0.into()).unwrap(), 0.into());
+            assert_eq!(
+                state.load(Address::zero(), 0.into()).unwrap(),
+                42.into()
+            );
+        }
+    }
+
+    /// Returns code that stores 42 at word 0, STATICCALLs the precompile at
+    /// the address with it and returns word 0 ++ the output ++ the flag.
+    fn precompile_caller(address: &str) -> String {
+        format!(
+            "602a600052602060206020600060{}61fffffa60405260606000f3",
+            address
+        )
+    }
+
+    #[test]
+    fn test_call_precompile() {
+        let code = precompile_caller("04");
+        let (output, _) = exec(&code, 100000).result.unwrap();
+        assert_eq!(output, [word(42), word(42), word(1)].concat());
+        let code = precompile_caller("02");
+        let (output, _) = exec(&code, 100000).result.unwrap();
+        let hash = <sha2::Sha256 as sha2::Digest>::digest(word(42));
+        assert_eq!(output, [word(42), hash.to_vec(), word(1)].concat());
+        // Precompiles are warm from the start, see EIP-2929.
+        let res = exec_with(Spec::Berlin, &precompile_caller("04"), 100000);
+        let call = 100 + 3 + 15 + 3;
+        assert_eq!(res.gas_used, 3 + 3 + 6 + 6 * 3 + call + 3 + 6 + 3 + 3);
+    }
+
+    #[test]
+    fn test_call_precompile_failure() {
+        // BLAKE2 F fails on inputs of the wrong size, consuming all the gas.
+        let code = precompile_caller("09");
+        let res = exec_with(Spec::Istanbul, &code, 100000);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, [word(42), word(0), word(0)].concat());
+        assert!(res.gas_used > 0xffff);
+    }
+
+    #[test]
+    fn test_run_precompile() {
+        let mut state = State::new(MemoryDB::new());
+        let mut env = test_env();
+        env.tx.calldata = vec![1, 2, 3];
+        let address = Address::from_low_u64_be(4);
+        let res = run(
+            address,
+            &[],
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            SPEC,
+            100,
+        );
+        assert_eq!(res.result, Ok((vec![1, 2, 3], vec![])));
+        assert_eq!(res.gas_used, 18);
+        // MODEXP is only available since Byzantium, before that the address
+        // is an account without code. Computes 2^3 % 5.
+        env.tx.calldata = [word(1), word(1), word(1), vec![2, 3, 5]].concat();
+        let address = Address::from_low_u64_be(5);
+        let spec = Spec::SpuriousDragon;
+        let res = run(
+            address,
+            &[],
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            spec,
+            100,
+        );
+        assert_eq!(res.result, Ok((vec![], vec![])));
+        let spec = Spec::Byzantium;
+        let res = run(
+            address,
+            &[],
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            spec,
+            100,
+        );
+        assert_eq!(res.result, Ok((vec![3], vec![])));
+    }
+
+    #[test]
+    fn test_call_depth_limit() {
+        let mut state = state_with_callee(STORE_AND_RETURN);
+        let code = hex::decode(caller_code("f1")).unwrap();
+        let env = test_env();
+        let (res, _) = execute(Context {
+            address: Address::zero(),
+            caller: Address::zero(),
+            code: &code,
+            jumps: Rc::new(JumpTable::analyze(&code)),
+            calldata: &[],
+            value: U256::zero(),
+            state: &mut state,
+            env: &env,
+            precompiles: &Precompiles::new(),
+            depth: MAX_CALL_DEPTH,
+            is_static: false,
+            spec: SPEC,
+            pc: 0,
+            mem: Mem::new(),
+            stack: Stack::new(),
+            logs: Vec::new(),
+            returndata: Vec::new(),
+            gas: Gas::new(100000),
+        });
+        let (output, _) = res.unwrap();
+        assert_eq!(output, [word(0), word(0)].concat());
+    }
+
+    // Init code that deploys runtime code returning the 32-byte word 42.
+    const INITCODE: &str = "69602a60005260206000f3600052600a6016f3";
+    const RUNTIME: &str = "602a60005260206000f3";
+
+    #[test]
+    fn test_create_address() {
+        let sender =
+            "6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0".parse().unwrap();
+        let expected = "cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d".parse();
+        assert_eq!(create_address(sender, 0), expected.unwrap());
+        let expected = "343c43a37d37dff08ae8c4a11544c718abb4fcf8".parse();
+        assert_eq!(create_address(sender, 1), expected.unwrap());
+    }
+
+    #[test]
+    fn test_create2_address() {
+        let expected = "4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38".parse();
+        let address = create2_address(Address::zero(), H256::zero(), &[0]);
+        assert_eq!(address, expected.unwrap());
+        let sender =
+            "deadbeef00000000000000000000000000000000".parse().unwrap();
+        let expected = "b928f69bb1d91cd65274e3c79d8986362984fda3".parse();
+        let address = create2_address(sender, H256::zero(), &[0]);
+        assert_eq!(address, expected.unwrap());
+    }
+
+    #[test]
+    fn test_create() {
+        let mut state = State::new(MemoryDB::new());
+        // PUSH19 initcode, PUSH1 0, MSTORE, CREATE(0, 13, 19), return address
+        let code =
+            format!("72{}6000526013600d6000f060005260206000f3", INITCODE);
+        let (output, _) = exec_in(&mut state, &code, 1000000).result.unwrap();
+        let address = create_address(Address::zero(), 0);
+        assert_eq!(Address::from_slice(&output[12..]), address);
+        let code = state.load_code(address).unwrap();
+        assert_eq!(code, hex::decode(RUNTIME).unwrap());
+        assert_eq!(state.load_account(address).unwrap().nonce, 1);
+        assert_eq!(state.load_account(Address::zero()).unwrap().nonce, 1);
+    }
+
+    #[test]
+    fn test_create2() {
+        let mut state = State::new(MemoryDB::new());
+        // PUSH19 initcode, PUSH1 0, MSTORE, CREATE2(0, 13, 19, 7)
+        let code =
+            format!("72{}60005260076013600d6000f560005260206000f3", INITCODE);
+        let (output, _) = exec_in(&mut state, &code, 1000000).result.unwrap();
+        let initcode = hex::decode(INITCODE).unwrap();
+        let salt = H256::from_low_u64_be(7);
+        let address = create2_address(Address::zero(), salt, &initcode);
+        assert_eq!(Address::from_slice(&output[12..]), address);
+        let runtime = state.load_code(address).unwrap();
+        assert_eq!(runtime, hex::decode(RUNTIME).unwrap());
+
+        // Creating at the same address again collides and pushes zero.
+        let (output, _) = exec_in(&mut state, &code, 1000000).result.unwrap();
+        assert_eq!(output, word(0));
+    }
+
+    #[test]
+    fn test_create_in_static_context() {
+        let mut state = state_with_callee("600060006000f000");
+        let res = exec_in(&mut state, &caller_code("fa"), 100000);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, [word(0), word(0)].concat());
+    }
+
+    #[test]
+    fn test_deploy() {
+        let mut state = State::new(MemoryDB::new());
+        let initcode = hex::decode(INITCODE).unwrap();
+        let address = Address::from_low_u64_be(0xcc);
+        let res = deploy(
+            address,
+            &initcode,
+            &mut state,
+            &test_env(),
+            &Precompiles::new(),
+            SPEC,
+            100000,
+        );
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, hex::decode(RUNTIME).unwrap());
+        assert_eq!(res.gas_used, 3 + 3 + 3 + 3 + 3 + 3 + 200 * 10);
+        assert_eq!(state.load_code(address).unwrap(), output);
+        let res = deploy(
+            address,
+            &initcode,
+            &mut state,
+            &test_env(),
+            &Precompiles::new(),
+            SPEC,
+            100000,
+        );
+        assert_eq!(res.result, Err(Error::CreateCollision));
+    }
+
+    #[test]
+    fn test_deploy_code_size_limit() {
+        let mut state = State::new(MemoryDB::new());
+        let address = Address::from_low_u64_be(0xcc);
+        // PUSH2 0x6001, PUSH1 0, RETURN
+        let initcode = hex::decode("6160016000f3").unwrap();
+        let res = deploy(
+            address,
+            &initcode,
+            &mut state,
+            &test_env(),
+            &Precompiles::new(),
+            SPEC,
+            1000000,
+        );
+        assert_eq!(res.result, Err(Error::CodeSizeExceeded));
+        // PUSH2 0x6000, PUSH1 0, RETURN
+        let initcode = hex::decode("6160006000f3").unwrap();
+        let address = Address::from_low_u64_be(0xcd);
+        let res = deploy(
+            address,
+            &initcode,
+            &mut state,
+            &test_env(),
+            &Precompiles::new(),
+            SPEC,
+            10000000,
+        );
+        assert!(res.result.is_ok());
+    }
+
+    /// Sets the balance of the account at the given address.
+    fn fund(state: &mut State<MemoryDB>, address: Address, balance: u64) {
+        let mut account = state.load_account(address).unwrap();
+        account.balance = balance.into();
+        state.store_account(address, account);
+    }
+
+    #[test]
+    fn test_callvalue_and_balances() {
+        let mut state = State::new(MemoryDB::new());
+        fund(&mut state, Address::zero(), 500);
+        fund(&mut state, CALLEE.parse().unwrap(), 77);
+        // CALLVALUE, SELFBALANCE, BALANCE(0xaa), return the three words
+        let code = "346000524760205260aa3160405260606000f3";
+        let code = hex::decode(code).unwrap();
+        let mut env = test_env();
+        env.tx.value = 9.into();
+        let res = run(
+            Address::zero(),
+            &code,
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            SPEC,
+            100000,
+        );
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, [word(9), word(500), word(77)].concat());
+    }
+
+    // CALL(0xffff, 0xaa, 100, 0, 0, 0, 0) and return the success flag.
+    const CALL_WITH_VALUE: &str =
+        "6000600060006000606460aa61fffff160005260206000f3";
+
+    #[test]
+    fn test_call_with_value() {
+        let mut state = state_with_callee("00");
+        fund(&mut state, Address::zero(), 150);
+        let (output, _) =
+            exec_in(&mut state, CALL_WITH_VALUE, 100000).result.unwrap();
+        assert_eq!(output, word(1));
+        assert_eq!(
+            state.load_account(Address::zero()).unwrap().balance,
+            50.into()
+        );
+        let callee = CALLEE.parse().unwrap();
+        assert_eq!(state.load_account(callee).unwrap().balance, 100.into());
+
+        // Not enough balance left for a second transfer.
+        let (output, _) =
+            exec_in(&mut state, CALL_WITH_VALUE, 100000).result.unwrap();
+        assert_eq!(output, word(0));
+        assert_eq!(
+            state.load_account(Address::zero()).unwrap().balance,
+            50.into()
+        );
+        assert_eq!(state.load_account(callee).unwrap().balance, 100.into());
+    }
+
+    #[test]
+    fn test_call_without_code() {
+        for opcode in ["f1", "f2", "f4", "fa"] {
+            let res = exec(&caller_code(opcode), 100000);
+            let (output, _) = res.result.unwrap();
+            assert_eq!(output, [word(0), word(1)].concat(), "{}", opcode);
+        }
+        // The value moves to the account even though no code runs.
+        let mut state = State::new(MemoryDB::new());
+        fund(&mut state, Address::zero(), 150);
+        let (output, _) =
+            exec_in(&mut state, CALL_WITH_VALUE, 100000).result.unwrap();
+        assert_eq!(output, word(1));
+        let callee = CALLEE.parse().unwrap();
+        assert_eq!(state.load_account(callee).unwrap().balance, 100.into());
+        assert_eq!(
+            state.load_account(Address::zero()).unwrap().balance,
+            50.into()
+        );
+    }
+
+    #[test]
+    fn test_call_with_value_reverted() {
+        let mut state = state_with_callee(STORE_AND_REVERT);
+        fund(&mut state, Address::zero(), 150);
+        let (output, _) =
+            exec_in(&mut state, CALL_WITH_VALUE, 100000).result.unwrap();
+        assert_eq!(output, word(0));
+        assert_eq!(
+            state.load_account(Address::zero()).unwrap().balance,
+            150.into()
+        );
+        let callee = CALLEE.parse().unwrap();
+        assert_eq!(state.load_account(callee).unwrap().balance, 0.into());
+    }
+
+    #[test]
+    fn test_create_with_value() {
+        let mut state = State::new(MemoryDB::new());
+        fund(&mut state, Address::zero(), 10);
+        // PUSH19 initcode, PUSH1 0, MSTORE, CREATE(4, 13, 19), return address
+        let code =
+            format!("72{}6000526013600d6004f060005260206000f3", INITCODE);
+        let (output, _) = exec_in(&mut state, &code, 1000000).result.unwrap();
+        let address = create_address(Address::zero(), 0);
+        assert_eq!(Address::from_slice(&output[12..]), address);
+        assert_eq!(state.load_account(address).unwrap().balance, 4.into());
+        assert_eq!(
+            state.load_account(Address::zero()).unwrap().balance,
+            6.into()
+        );
+    }
+
+    #[test]
+    fn test_byte() {
+        let value =
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+        for (index, expected) in [(0, 0), (1, 1), (31, 31), (32, 0), (255, 0)] {
+            // PUSH32 value, PUSH2 index, BYTE, PUSH1 0, MSTORE, RETURN 32
+            let code = format!("7f{}61{:04x}1a60005260206000f3", value, index);
+            let (output, _) = exec(&code, 100000).result.unwrap();
+            assert_eq!(output, word(expected));
+        }
+        // Index 2^256 - 1 is out of range as well.
+        let code = format!("7f{}600019{}", value, "1a60005260206000f3");
+        let (output, _) = exec(&code, 100000).result.unwrap();
+        assert_eq!(output, word(0));
+    }
+
+    #[test]
+    fn test_signextend_and_sar() {
+        // PUSH1 0xff, PUSH1 0, SIGNEXTEND, PUSH1 4, SAR, return the word
+        let code = "60ff60000b60041d60005260206000f3";
+        let (output, _) = exec(code, 100000).result.unwrap();
+        assert_eq!(output, vec![0xff; 32]);
+    }
+
+    fn eval(opcode: u8, args: &[U256]) -> Vec<u8> {
+        let mut code = String::new();
+        for arg in args.iter().rev() {
+            code += &format!("7f{}", hex::encode(u256_to_word(*arg)));
+        }
+        code += &format!("{:02x}60005260206000f3", opcode);
+        exec(&code, 100000).result.unwrap().0
+    }
+
+    fn u256_to_word(value: U256) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        value.to_big_endian(&mut out);
+        out
+    }
+
+    #[test]
+    fn test_arithmetic_conformance() {
+        let max = U256::MAX;
+        let min = U256::one() << 255;
+        let neg = |x: u64| U256::zero().overflowing_sub(x.into()).0;
+        let n = |x: u64| U256::from(x);
+        #[rustfmt::skip]
+        let cases: Vec<(u8, Vec<U256>, U256)> = vec![
+            // ADD
+            (0x01, vec![n(1), n(2)], n(3)),
+            (0x01, vec![max, n(1)], n(0)),
+            (0x01, vec![max, max], max - 1),
+            // MUL
+            (0x02, vec![n(6), n(7)], n(42)),
+            (0x02, vec![max, n(2)], max - 1),
+            (0x02, vec![min, n(2)], n(0)),
+            (0x02, vec![max, max], n(1)),
+            // SUB
+            (0x03, vec![n(5), n(3)], n(2)),
+            (0x03, vec![n(0), n(1)], max),
+            (0x03, vec![n(3), n(5)], neg(2)),
+            // DIV
+            (0x04, vec![n(7), n(2)], n(3)),
+            (0x04, vec![n(7), n(0)], n(0)),
+            (0x04, vec![max, max], n(1)),
+            // SDIV
+            (0x05, vec![neg(8), n(2)], neg(4)),
+            (0x05, vec![neg(7), neg(2)], n(3)),
+            (0x05, vec![n(7), n(0)], n(0)),
+            (0x05, vec![min, neg(1)], min),
+            // MOD
+            (0x06, vec![n(7), n(3)], n(1)),
+            (0x06, vec![n(7), n(0)], n(0)),
+            // SMOD
+            (0x07, vec![neg(7), n(3)], neg(1)),
+            (0x07, vec![n(7), neg(3)], n(1)),
+            (0x07, vec![neg(7), n(0)], n(0)),
+            (0x07, vec![min, neg(1)], n(0)),
+            // ADDMOD
+            (0x08, vec![n(5), n(6), n(4)], n(3)),
+            (0x08, vec![max, n(2), n(2)], n(1)),
+            (0x08, vec![max, max, max], n(0)),
+            (0x08, vec![n(5), n(6), n(0)], n(0)),
+            // MULMOD
+            (0x09, vec![n(5), n(6), n(4)], n(2)),
+            (0x09, vec![max, max, n(12)], n(9)),
+            (0x09, vec![max, n(2), max], n(0)),
+            (0x09, vec![n(5), n(6), n(0)], n(0)),
+            // EXP
+            (0x0a, vec![n(2), n(10)], n(1024)),
+            (0x0a, vec![n(2), n(256)], n(0)),
+            (0x0a, vec![n(0), n(0)], n(1)),
+            (0x0a, vec![max, n(2)], n(1)),
+            // SHL
+            (0x1b, vec![n(1), n(1)], n(2)),
+            (0x1b, vec![n(255), n(1)], min),
+            (0x1b, vec![n(256), n(1)], n(0)),
+            (0x1b, vec![max, max], n(0)),
+            // SHR
+            (0x1c, vec![n(1), n(2)], n(1)),
+            (0x1c, vec![n(255), min], n(1)),
+            (0x1c, vec![n(256), max], n(0)),
+            (0x1c, vec![max, max], n(0)),
+            // SAR
+            (0x1d, vec![n(1), neg(2)], neg(1)),
+            (0x1d, vec![n(256), min], max),
+            (0x1d, vec![max, n(1)], n(0)),
+        ];
+        for (opcode, args, expected) in cases {
+            assert_eq!(
+                eval(opcode, &args),
+                u256_to_word(expected),
+                "opcode {:#04x} with {:?}",
+                opcode,
+                args
+            );
         }
     }
+
+    #[test]
+    fn test_calldatacopy() {
+        let mut env = test_env();
+        env.tx.calldata = vec![1, 2, 3, 4];
+        // CALLDATACOPY(dst=0, offset=2, len=4),
+        // CALLDATACOPY(dst=4, offset=2^256-1, len=1), RETURN memory[0..8]
+        let code =
+            hex::decode("60046002600037600160001960043760086000f3").unwrap();
+        let mut state = State::new(MemoryDB::new());
+        let res = run(
+            Address::zero(),
+            &code,
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            SPEC,
+            100000,
+        );
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, vec![3, 4, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_zero_length_areas() {
+        // CALLDATACOPY, RETURNDATACOPY, MCOPY, KECCAK256, STATICCALL and
+        // RETURN of zero bytes, all at offset 2^256-1
+        let code = [
+            "6000600060001937",
+            "600060006000193e",
+            "60006000196000195e",
+            "600060001920",
+            "50",
+            "60006000196000600019600461fffffa",
+            "50",
+            "6000600019f3",
+        ]
+        .concat();
+        let res = exec(&code, 100000);
+        assert_eq!(res.result, Ok((vec![], vec![])));
+    }
+
+    #[test]
+    fn test_calldataload_padding() {
+        let mut env = test_env();
+        env.tx.calldata = vec![1, 2, 3, 4];
+        // MSTORE(0, CALLDATALOAD(1)), MSTORE(32, CALLDATALOAD(2^256-1)),
+        // RETURN memory[0..64]
+        let code = hex::decode("6001356000526000193560205260406000f3").unwrap();
+        let mut state = State::new(MemoryDB::new());
+        let res = run(
+            Address::zero(),
+            &code,
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            SPEC,
+            100000,
+        );
+        let (output, _) = res.result.unwrap();
+        let mut expected = vec![0u8; 64];
+        expected[..3].copy_from_slice(&[2, 3, 4]);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_extcodehash() {
+        let mut state = state_with_callee("00");
+        fund(&mut state, Address::from_low_u64_be(0xbb), 1);
+        // EXTCODEHASH of 0xaa, 0xbb and 0xcc, return the three words
+        let code = "60aa3f60005260bb3f60205260cc3f60405260606000f3";
+        let res = exec_in(&mut state, code, 100000);
+        let (output, _) = res.result.unwrap();
+        let expected = [
+            code_hash(&[0x00]).as_bytes(),
+            KECCAK_EMPTY.as_bytes(),
+            &[0; 32],
+        ]
+        .concat();
+        assert_eq!(output, expected);
+        let res = exec_with(Spec::Istanbul, "60aa3f00", 100000);
+        assert_eq!(res.gas_used, 3 + 700);
+    }
+
+    #[test]
+    fn test_blobhash() {
+        let mut env = test_env();
+        env.tx.blob_hashes = vec![H256::repeat_byte(1)];
+        // BLOBHASH(0), BLOBHASH(1), return the two words
+        let code = hex::decode("60004960005260014960205260406000f3").unwrap();
+        let mut state = State::new(MemoryDB::new());
+        let res = run(
+            Address::zero(),
+            &code,
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            SPEC,
+            100000,
+        );
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, [[1; 32], [0; 32]].concat());
+    }
+
+    #[test]
+    fn test_gated_opcodes() {
+        let forks = [
+            Spec::Frontier,
+            Spec::Homestead,
+            Spec::TangerineWhistle,
+            Spec::SpuriousDragon,
+            Spec::Byzantium,
+            Spec::Constantinople,
+            Spec::Petersburg,
+            Spec::Istanbul,
+            Spec::Berlin,
+            Spec::London,
+            Spec::Merge,
+            Spec::Shanghai,
+            Spec::Cancun,
+            Spec::Prague,
+        ];
+        // Every opcode missing from Frontier runs once its fork is active.
+        let gated = (0..=u8::MAX).filter(|op| !Spec::Frontier.has_opcode(*op));
+        for opcode in gated {
+            for spec in forks {
+                // Enough zero arguments for any opcode, then the opcode.
+                let code = format!("{}{:02x}", "6000".repeat(7), opcode);
+                let res = exec_with(spec, &code, 1000000);
+                let invalid = res.result == Err(Error::InvalidOpcode(opcode));
+                assert_eq!(
+                    invalid,
+                    !spec.has_opcode(opcode),
+                    "opcode {:#04x} in {:?}",
+                    opcode,
+                    spec
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_codecopy_and_codesize() {
+        // MSTORE8(0, CODESIZE), CODECOPY(dst=1, offset=14, len=4),
+        // RETURN memory[0..5]
+        let res = exec("386000536004600e60013960056000f3", 100000);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, vec![16, 0x00, 0xf3, 0, 0]);
+        assert_eq!(res.gas_used, 2 + 3 + 3 + 3 + 9 + 6 + 6);
+    }
+
+    #[test]
+    fn test_mstore8() {
+        // MSTORE8(0, 0x1234), RETURN memory[0..1]
+        let res = exec("61123460005360016000f3", 100000);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, vec![0x34]);
+    }
+
+    #[test]
+    fn test_mcopy() {
+        // MSTORE(0, 0x0102), MCOPY(dst=32, src=30, len=2), RETURN word at 32
+        let res = exec("6101026000526002601e60205e60206020f3", 100000);
+        let (output, _) = res.result.unwrap();
+        let mut expected = vec![0u8; 32];
+        expected[..2].copy_from_slice(&[1, 2]);
+        assert_eq!(output, expected);
+        assert_eq!(res.gas_used, 3 + 3 + 6 + 9 + 6 + 3 + 6);
+    }
+
+    // Reverts with the 32-byte word 7 as revert data.
+    const REVERT_WITH_DATA: &str = "600760005260206000fd";
+    // Calls the callee without copying any output, then returns
+    // RETURNDATASIZE followed by the first word of the return data buffer.
+    const RETURNDATA_CALLER: &str = concat!(
+        "6000600060006000600060aa61fffff150",
+        "3d600052602060006020",
+        "3e60406000f3"
+    );
+
+    #[test]
+    fn test_returndata() {
+        for (callee, size) in [(STORE_AND_RETURN, 32), (REVERT_WITH_DATA, 32)] {
+            let mut state = state_with_callee(callee);
+            let res = exec_in(&mut state, RETURNDATA_CALLER, 100000);
+            let (output, _) = res.result.unwrap();
+            assert_eq!(output, [word(size), word(7)].concat());
+        }
+    }
+
+    #[test]
+    fn test_returndatacopy_out_of_bound() {
+        // RETURNDATACOPY(dst=0, offset=0, len=1) with an empty buffer
+        let res = exec("6001600060003e", 100000);
+        assert_eq!(res.result, Err(Error::ReturnDataOutOfBound));
+        assert_eq!(res.gas_left, 0);
+        // RETURNDATACOPY(dst=0, offset=2^256-1, len=0)
+        let res = exec("600060001960003e00", 100000);
+        assert_eq!(res.result, Err(Error::ReturnDataOutOfBound));
+        // RETURNDATACOPY(dst=0, offset=0, len=0), RETURNDATASIZE, STOP
+        let res = exec("6000600060003e3d00", 100000);
+        assert_eq!(res.result, Ok((vec![], vec![])));
+    }
+
+    #[test]
+    fn test_jump() {
+        // PUSH1 4, JUMP, INVALID, JUMPDEST, PUSH1 1, PUSH1 0, MSTORE8,
+        // RETURN memory[0..1]
+        let res = exec("600456fe5b600160005360016000f3", 100000);
+        assert_eq!(res.result, Ok((vec![1], vec![])));
+    }
+
+    #[test]
+    fn test_jump_into_push_data() {
+        // PUSH1 3, JUMP, PUSH1 0x5b, STOP
+        let res = exec("600356605b00", 100000);
+        let to = U256::from(3);
+        assert_eq!(res.result, Err(Error::InvalidJump { from: 2, to }));
+        assert_eq!(res.gas_left, 0);
+    }
+
+    #[test]
+    fn test_jump_invalid_targets() {
+        // PUSH1 4, JUMP, STOP, STOP (not a JUMPDEST)
+        let res = exec("6004560000", 100000);
+        let to = U256::from(4);
+        assert_eq!(res.result, Err(Error::InvalidJump { from: 2, to }));
+        // PUSH1 0, NOT, JUMP (past the end of the code)
+        let res = exec("60001956", 100000);
+        let to = U256::MAX;
+        assert_eq!(res.result, Err(Error::InvalidJump { from: 3, to }));
+        // PUSH1 0, PUSH1 7, JUMPI, STOP: condition is false, no jump.
+        let res = exec("600060075700", 100000);
+        assert_eq!(res.result, Ok((vec![], vec![])));
+        // PUSH1 1, PUSH1 7, JUMPI, STOP
+        let res = exec("600160075700", 100000);
+        let to = U256::from(7);
+        assert_eq!(res.result, Err(Error::InvalidJump { from: 4, to }));
+    }
+
+    #[test]
+    fn test_environment() {
+        let mut env = test_env();
+        env.tx.caller = Address::from_low_u64_be(0x0119);
+        env.tx.gasprice = 7.into();
+        env.block.coinbase = Address::from_low_u64_be(0xc014);
+        env.block.gaslimit = 30_000_000.into();
+        env.block.difficulty = 2.into();
+        env.block.prevrandao = H256::from_low_u64_be(0x5eed);
+        env.block.basefee = 3.into();
+        env.block.blob_basefee = 4.into();
+        // ORIGIN, GASPRICE, COINBASE, PREVRANDAO, GASLIMIT, BASEFEE,
+        // BLOBBASEFEE, then store each word in order and return them all.
+        let mut code = String::from("323a414445484a");
+        for idx in (0..7).rev() {
+            code += &format!("60{:02x}52", idx * 32);
+        }
+        code += "60e06000f3";
+        let code = hex::decode(code).unwrap();
+        let mut state = State::new(MemoryDB::new());
+        let res = run(
+            Address::zero(),
+            &code,
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            SPEC,
+            100000,
+        );
+        let (output, _) = res.result.unwrap();
+        let expected = [
+            word(0x0119),
+            word(7),
+            word(0xc014),
+            word(0x5eed),
+            word(30_000_000),
+            word(3),
+            word(4),
+        ];
+        assert_eq!(output, expected.concat());
+        let res = run(
+            Address::zero(),
+            &code,
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            Spec::London,
+            100000,
+        );
+        assert_eq!(res.result, Err(Error::InvalidOpcode(0x4a)));
+        // DIFFICULTY, PUSH1 0, MSTORE, RETURN word
+        let code = hex::decode("4460005260206000f3").unwrap();
+        let res = run(
+            Address::zero(),
+            &code,
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            Spec::London,
+            100000,
+        );
+        assert_eq!(res.result.unwrap().0, word(2));
+    }
+
+    #[test]
+    fn test_gas_opcode() {
+        // GAS, PUSH1 0, MSTORE, RETURN word
+        let res = exec("5a60005260206000f3", 1000);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output, word(1000 - 2));
+    }
+
+    #[test]
+    fn test_blockhash() {
+        let mut hashes = MemoryDB::new();
+        for number in [0, 43, 44, 299, 300] {
+            hashes.set_block_hash(number, H256::from_low_u64_be(number + 1));
+        }
+        let mut state = State::new(MemoryDB::new());
+        state.set_block_hashes(Box::new(hashes));
+        let mut env = test_env();
+        env.block.number = 300.into();
+        for (number, expected) in [(299, 300), (44, 45), (43, 0), (300, 0)] {
+            // BLOCKHASH(number), return the word
+            let code = format!("61{:04x}4060005260206000f3", number);
+            let code = hex::decode(code).unwrap();
+            let res = run(
+                Address::zero(),
+                &code,
+                &mut state,
+                &env,
+                &Precompiles::new(),
+                SPEC,
+                1000,
+            );
+            assert_eq!(res.result.unwrap().0, word(expected));
+            assert_eq!(res.gas_used, 3 + 20 + 3 + 3 + 3 + 3 + 3);
+        }
+        // BLOCKHASH(2^256 - 1), return the word
+        let code = hex::decode("6000194060005260206000f3").unwrap();
+        let res = run(
+            Address::zero(),
+            &code,
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            SPEC,
+            1000,
+        );
+        assert_eq!(res.result.unwrap().0, word(0));
+    }
+
+    /// A database whose storage reads always fail.
+    struct FailingDB(MemoryDB);
+
+    impl Database for FailingDB {
+        fn get_account(&self, address: Address) -> Result<Account, DbError> {
+            self.0.get_account(address)
+        }
+
+        fn set_account(
+            &mut self,
+            address: Address,
+            account: Account,
+        ) -> Result<(), DbError> {
+            self.0.set_account(address, account)
+        }
+
+        fn get_code(&self, hash: H256) -> Result<Option<Vec<u8>>, DbError> {
+            self.0.get_code(hash)
+        }
+
+        fn set_code(&mut self, code: Vec<u8>) -> Result<(), DbError> {
+            self.0.set_code(code)
+        }
+
+        fn get(&self, _: Address, _: U256) -> Result<U256, DbError> {
+            Err(DbError::Backend("read failed".into()))
+        }
+
+        fn set(&mut self, _: Address, _: U256, _: U256) -> Result<(), DbError> {
+            Err(DbError::Backend("write failed".into()))
+        }
+
+        fn accounts(&self) -> DbIter<'_, Address> {
+            self.0.accounts()
+        }
+
+        fn storage(&self, _: Address) -> DbIter<'_, (U256, U256)> {
+            let err = DbError::Backend("read failed".into());
+            Box::new(std::iter::once(Err(err)))
+        }
+
+        fn write_batch(&mut self, _: Vec<WriteOp>) -> Result<(), DbError> {
+            Err(DbError::Backend("write failed".into()))
+        }
+    }
+
+    #[test]
+    fn test_database_error() {
+        let err = Err(Error::Database(DbError::Backend("read failed".into())));
+        let mut state = State::new(FailingDB(MemoryDB::new()));
+        // PUSH1 0, SLOAD
+        let code = hex::decode("600054").unwrap();
+        let res = run(
+            Address::zero(),
+            &code,
+            &mut state,
+            &test_env(),
+            &Precompiles::new(),
+            SPEC,
+            5000,
+        );
+        assert_eq!(res.result, err);
+        assert_eq!(res.gas_left, 0);
+        // The error of a child frame aborts the whole execution.
+        let code = hex::decode(STORE_AND_RETURN).unwrap();
+        state.store_code(CALLEE.parse().unwrap(), code).unwrap();
+        let code = hex::decode(caller_code("f1")).unwrap();
+        let env = test_env();
+        let res = run(
+            Address::zero(),
+            &code,
+            &mut state,
+            &env,
+            &Precompiles::new(),
+            SPEC,
+            100000,
+        );
+        assert_eq!(res.result, err);
+        // A failed commit drops all the pending changes.
+        let write_err = DbError::Backend("write failed".into());
+        assert_eq!(state.commit(), Err(write_err));
+        let callee = CALLEE.parse().unwrap();
+        assert!(!state.load_account(callee).unwrap().has_code());
+    }
 }
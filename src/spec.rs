@@ -0,0 +1,137 @@
+use crate::gas::Schedule;
+
+/// Ethereum mainnet hardforks in activation order. Each fork includes the
+/// rules of all the forks before it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Spec {
+    Frontier,
+    Homestead,
+    TangerineWhistle,
+    SpuriousDragon,
+    Byzantium,
+    /// Treated as Petersburg, since EIP-1283 was never activated on mainnet.
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    Merge,
+    Shanghai,
+    #[default]
+    Cancun,
+    /// Only EIP-2935 block hash history is implemented so far.
+    Prague,
+}
+
+impl Spec {
+    /// The most recent hardfork fully supported by the runtime.
+    pub const LATEST: Spec = Spec::Cancun;
+
+    /// Returns whether the rules introduced by the given fork are active.
+    pub fn enabled(self, fork: Spec) -> bool {
+        self >= fork
+    }
+
+    /// Returns whether the given opcode is defined in this fork.
+    pub fn has_opcode(self, opcode: u8) -> bool {
+        match opcode {
+            0xf4 => self.enabled(Spec::Homestead),
+            0x3d | 0x3e | 0xfa | 0xfd => self.enabled(Spec::Byzantium),
+            0x1b..=0x1d | 0x3f | 0xf5 => self.enabled(Spec::Constantinople),
+            0x46 | 0x47 => self.enabled(Spec::Istanbul),
+            0x48 => self.enabled(Spec::London),
+            0x5f => self.enabled(Spec::Shanghai),
+            0x49 | 0x4a | 0x5c..=0x5e => self.enabled(Spec::Cancun),
+            _ => true,
+        }
+    }
+
+    /// Returns the gas costs that apply in this fork.
+    pub fn schedule(self) -> Schedule {
+        let mut schedule = Schedule {
+            sload: 50,
+            balance: 20,
+            extcode: 20,
+            extcodehash: 400,
+            call: 40,
+            expbyte: 10,
+            sreset: 5000,
+            sclear_refund: 15000,
+            txdatanonzero: 68,
+            max_refund_quotient: 2,
+        };
+        if self.enabled(Spec::TangerineWhistle) {
+            schedule.sload = 200;
+            schedule.balance = 400;
+            schedule.extcode = 700;
+            schedule.call = 700;
+        }
+        if self.enabled(Spec::SpuriousDragon) {
+            schedule.expbyte = 50;
+        }
+        if self.enabled(Spec::Istanbul) {
+            schedule.sload = 800;
+            schedule.balance = 700;
+            schedule.extcodehash = 700;
+            schedule.txdatanonzero = 16;
+        }
+        if self.enabled(Spec::Berlin) {
+            // Costs of warm accesses, cold ones are charged extra, see EIP-2929.
+            schedule.sload = 100;
+            schedule.balance = 100;
+            schedule.extcode = 100;
+            schedule.extcodehash = 100;
+            schedule.call = 100;
+            schedule.sreset = 5000 - 2100;
+        }
+        if self.enabled(Spec::London) {
+            schedule.sclear_refund = 4800;
+            schedule.max_refund_quotient = 5;
+        }
+        schedule
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enabled() {
+        assert!(Spec::Cancun.enabled(Spec::Frontier));
+        assert!(Spec::Berlin.enabled(Spec::Berlin));
+        assert!(!Spec::Istanbul.enabled(Spec::Berlin));
+        assert_eq!(Spec::default(), Spec::LATEST);
+    }
+
+    #[test]
+    fn test_has_opcode() {
+        assert!(Spec::Frontier.has_opcode(0x01));
+        assert!(!Spec::Frontier.has_opcode(0xf4));
+        assert!(Spec::Homestead.has_opcode(0xf4));
+        assert!(!Spec::SpuriousDragon.has_opcode(0xfd));
+        assert!(Spec::Byzantium.has_opcode(0xfd));
+        assert!(!Spec::Byzantium.has_opcode(0x1b));
+        assert!(Spec::Constantinople.has_opcode(0xf5));
+        assert!(!Spec::Petersburg.has_opcode(0x47));
+        assert!(!Spec::Berlin.has_opcode(0x48));
+        assert!(!Spec::Merge.has_opcode(0x5f));
+        assert!(Spec::Shanghai.has_opcode(0x5f));
+        assert!(!Spec::Shanghai.has_opcode(0x5e));
+        assert!(Spec::Cancun.has_opcode(0x5c));
+    }
+
+    #[test]
+    fn test_schedule() {
+        assert_eq!(Spec::Frontier.schedule().sload, 50);
+        assert_eq!(Spec::TangerineWhistle.schedule().call, 700);
+        assert_eq!(Spec::SpuriousDragon.schedule().expbyte, 50);
+        assert_eq!(Spec::Istanbul.schedule().txdatanonzero, 16);
+        assert_eq!(Spec::Constantinople.schedule().extcodehash, 400);
+        assert_eq!(Spec::Istanbul.schedule().extcodehash, 700);
+        assert_eq!(Spec::Berlin.schedule().sreset, 2900);
+        assert_eq!(Spec::Berlin.schedule().sclear_refund, 15000);
+        assert_eq!(Spec::London.schedule().sclear_refund, 4800);
+        assert_eq!(Spec::Cancun.schedule().max_refund_quotient, 5);
+    }
+}
@@ -24,7 +24,8 @@ impl Stack {
     /// Pushes a new u256 value to the stack.
     pub fn push_u256(&mut self, value: U256) -> Result<(), Error> {
         if self.0.len() < MAX_SIZE {
-            Ok(self.0.push(value))
+            self.0.push(value);
+            Ok(())
         } else {
             Err(Error::StackOverflow)
         }
@@ -32,13 +33,13 @@ impl Stack {
 
     /// Pops a value and throws it away.
     pub fn pop(&mut self) -> Result<(), Error> {
-        self.pop_usize().map(|_| ())
+        self.pop_u256().map(|_| ())
     }
 
     /// Pops a value from the stack as a usize.
     pub fn pop_usize(&mut self) -> Result<usize, Error> {
         let value_256 = self.pop_u256()?;
-        if value_256 <= usize::max_value().into() {
+        if value_256 <= usize::MAX.into() {
             Ok(value_256.as_usize())
         } else {
             Err(Error::StackValueOutOfRange)
@@ -69,9 +70,10 @@ impl Stack {
     /// Swaps the first value and the (N+1)^th value of the stack.
     pub fn swap<const N: usize>(&mut self) -> Result<(), Error> {
         // TODO: Asserts N <= 32 at compile time
-        if self.0.len() >= N + 1 {
+        if self.0.len() > N {
             let len = self.0.len();
-            Ok(self.0.swap(len - 1, len - N - 1))
+            self.0.swap(len - 1, len - N - 1);
+            Ok(())
         } else {
             Err(Error::StackUnderflow)
         }
@@ -99,6 +101,8 @@ mod tests {
         stk.push_usize(21).unwrap();
         assert_eq!(stk.pop(), Ok(()));
         assert_eq!(stk.pop_usize(), Ok(20));
+        stk.push_u256(bv).unwrap();
+        assert_eq!(stk.pop(), Ok(()));
     }
 
     #[test]
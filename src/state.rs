@@ -1,11 +1,43 @@
-use crate::db::Database;
-use ethereum_types::U256;
-use std::collections::HashMap;
+use crate::analysis::{JumpCache, JumpTable};
+use crate::db::{BlockHashProvider, Database, DbError, WriteOp};
+use crate::types::KECCAK_EMPTY;
+use crate::types::{code_hash, Account, AccountDump, Error, StateDump};
+use ethereum_types::{Address, H256, U256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
 
+/// A change to the pending state, holding what is needed to undo it.
+enum JournalEntry {
+    Account(Address, Option<Account>),
+    Storage(Address, U256, Option<U256>),
+    Transient(Address, U256, Option<U256>),
+    AccountAccessed(Address),
+    SlotAccessed(Address, U256),
+    Code(H256),
+}
+
+/// A position in the journal that the state can be reverted to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Checkpoint(usize);
+
+/// The accounts of a database with the pending changes made on top of them,
+/// written back on commit.
 pub struct State<DB> {
     db: DB,
     // TODO: More optimization? don't need to flush on every commit
-    cache: HashMap<U256, U256>,
+    accounts: HashMap<Address, Account>,
+    cache: HashMap<(Address, U256), U256>,
+    // New code by hash, written to the database on commit.
+    codes: HashMap<H256, Vec<u8>>,
+    // Transaction-scoped data, cleared on commit and rollback.
+    transient: HashMap<(Address, U256), U256>,
+    accessed_accounts: HashSet<Address>,
+    accessed_slots: HashSet<(Address, U256)>,
+    // Changes made since the outermost open checkpoint.
+    journal: Vec<JournalEntry>,
+    checkpoints: usize,
+    jumps: JumpCache,
+    block_hashes: Option<Box<dyn BlockHashProvider>>,
 }
 
 impl<DB: Database> State<DB> {
@@ -13,31 +45,320 @@ impl<DB: Database> State<DB> {
     pub fn new(db: DB) -> Self {
         Self {
             db,
+            accounts: HashMap::new(),
             cache: HashMap::new(),
+            codes: HashMap::new(),
+            transient: HashMap::new(),
+            accessed_accounts: HashSet::new(),
+            accessed_slots: HashSet::new(),
+            journal: Vec::new(),
+            checkpoints: 0,
+            jumps: JumpCache::default(),
+            block_hashes: None,
+        }
+    }
+
+    /// Returns the account at the specified address from this state.
+    pub fn load_account(&self, address: Address) -> Result<Account, DbError> {
+        match self.accounts.get(&address) {
+            Some(account) => Ok(account.clone()),
+            None => self.db.get_account(address),
+        }
+    }
+
+    /// Stores the given account to the pending change set.
+    pub fn store_account(&mut self, address: Address, account: Account) {
+        let prev = self.accounts.insert(address, account);
+        self.record(JournalEntry::Account(address, prev));
+    }
+
+    /// Returns the code of the account at the specified address.
+    pub fn load_code(&self, address: Address) -> Result<Vec<u8>, DbError> {
+        Ok(self.load_code_hashed(address)?.1)
+    }
+
+    /// Returns the code hash of the account at the specified address along
+    /// with the code.
+    pub(crate) fn load_code_hashed(
+        &self,
+        address: Address,
+    ) -> Result<(H256, Vec<u8>), DbError> {
+        let hash = self.load_account(address)?.code_hash;
+        if hash == KECCAK_EMPTY {
+            return Ok((hash, Vec::new()));
+        }
+        if let Some(code) = self.codes.get(&hash) {
+            return Ok((hash, code.clone()));
+        }
+        // An account must never point at code that was not stored.
+        let code = self.db.get_code(hash)?.ok_or(DbError::Corrupted)?;
+        Ok((hash, code))
+    }
+
+    /// Sets the code of the account at the specified address.
+    pub fn store_code(
+        &mut self,
+        address: Address,
+        code: Vec<u8>,
+    ) -> Result<(), DbError> {
+        let mut account = self.load_account(address)?;
+        account.code_hash = code_hash(&code);
+        if !code.is_empty()
+            && self.codes.insert(account.code_hash, code).is_none()
+        {
+            self.record(JournalEntry::Code(account.code_hash));
+        }
+        self.store_account(address, account);
+        Ok(())
+    }
+
+    /// Moves the given amount of ether from one account to another.
+    pub(crate) fn transfer(
+        &mut self,
+        from: Address,
+        to: Address,
+        value: U256,
+    ) -> Result<(), Error> {
+        if value.is_zero() {
+            return Ok(());
+        }
+        let mut sender = self.load_account(from)?;
+        if sender.balance < value {
+            return Err(Error::InsufficientBalance);
         }
+        sender.balance -= value;
+        self.store_account(from, sender);
+        let mut recipient = self.load_account(to)?;
+        recipient.balance = recipient.balance.saturating_add(value);
+        self.store_account(to, recipient);
+        Ok(())
     }
 
-    /// Returns the value at the specified key from this state.
-    pub fn load(&self, key: U256) -> U256 {
-        match self.cache.get(&key) {
-            Some(value) => value.into(),
-            None => self.db.get(key),
+    /// Returns the valid jump destinations of the code with the given hash,
+    /// cached across transactions.
+    pub(crate) fn jump_table(
+        &mut self,
+        hash: H256,
+        code: &[u8],
+    ) -> Rc<JumpTable> {
+        self.jumps.get(hash, code)
+    }
+
+    /// Returns the value at the specified key of the account's storage.
+    pub fn load(&self, address: Address, key: U256) -> Result<U256, DbError> {
+        match self.cache.get(&(address, key)) {
+            Some(value) => Ok(value.into()),
+            None => self.db.get(address, key),
         }
     }
 
     /// Stores the given key-value to the pending change set.
-    pub fn store(&mut self, key: U256, value: U256) {
-        self.cache.insert(key, value);
+    pub fn store(&mut self, address: Address, key: U256, value: U256) {
+        let prev = self.cache.insert((address, key), value);
+        self.record(JournalEntry::Storage(address, key, prev));
+    }
+
+    /// Returns the hash of the block with the given number, or zero if there
+    /// is no block hash provider or it does not know the block.
+    pub fn block_hash(&self, number: u64) -> Result<H256, DbError> {
+        match &self.block_hashes {
+            Some(provider) => {
+                Ok(provider.block_hash(number)?.unwrap_or_default())
+            }
+            None => Ok(H256::zero()),
+        }
+    }
+
+    /// Sets the source of the hashes returned by block_hash.
+    pub(crate) fn set_block_hashes(
+        &mut self,
+        provider: Box<dyn BlockHashProvider>,
+    ) {
+        self.block_hashes = Some(provider);
+    }
+
+    /// Returns the value of the key as of the start of the transaction.
+    pub(crate) fn original(
+        &self,
+        address: Address,
+        key: U256,
+    ) -> Result<U256, DbError> {
+        self.db.get(address, key)
+    }
+
+    /// Returns the value at the specified key of the transient storage.
+    pub(crate) fn tload(&self, address: Address, key: U256) -> U256 {
+        self.transient
+            .get(&(address, key))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Stores the given key-value to the transient storage, see EIP-1153.
+    pub(crate) fn tstore(&mut self, address: Address, key: U256, value: U256) {
+        let prev = self.transient.insert((address, key), value);
+        self.record(JournalEntry::Transient(address, key, prev));
+    }
+
+    /// Marks the account as accessed and returns true if it was not yet.
+    pub(crate) fn access_account(&mut self, address: Address) -> bool {
+        let cold = self.accessed_accounts.insert(address);
+        if cold {
+            self.record(JournalEntry::AccountAccessed(address));
+        }
+        cold
+    }
+
+    /// Marks the storage slot as accessed and returns true if it was not yet.
+    pub(crate) fn access_slot(&mut self, address: Address, key: U256) -> bool {
+        let cold = self.accessed_slots.insert((address, key));
+        if cold {
+            self.record(JournalEntry::SlotAccessed(address, key));
+        }
+        cold
+    }
+
+    /// Opens a new checkpoint. Every checkpoint must later be closed with
+    /// either revert_to or discard, innermost first.
+    pub(crate) fn checkpoint(&mut self) -> Checkpoint {
+        self.checkpoints += 1;
+        Checkpoint(self.journal.len())
+    }
+
+    /// Undoes the changes made since the given checkpoint and closes it.
+    pub(crate) fn revert_to(&mut self, checkpoint: Checkpoint) {
+        while self.journal.len() > checkpoint.0 {
+            match self.journal.pop() {
+                Some(entry) => self.undo(entry),
+                None => break,
+            }
+        }
+        self.close_checkpoint();
+    }
+
+    /// Closes the given checkpoint and keeps its changes. They can still be
+    /// undone by reverting to an outer checkpoint.
+    pub(crate) fn discard(&mut self, checkpoint: Checkpoint) {
+        debug_assert!(checkpoint.0 <= self.journal.len());
+        self.close_checkpoint();
+    }
+
+    fn close_checkpoint(&mut self) {
+        self.checkpoints -= 1;
+        if self.checkpoints == 0 {
+            self.journal.clear();
+        }
+    }
+
+    /// Appends the change to the journal if any checkpoint is open.
+    fn record(&mut self, entry: JournalEntry) {
+        if self.checkpoints > 0 {
+            self.journal.push(entry);
+        }
+    }
+
+    fn undo(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::Account(address, Some(account)) => {
+                self.accounts.insert(address, account);
+            }
+            JournalEntry::Account(address, None) => {
+                self.accounts.remove(&address);
+            }
+            JournalEntry::Storage(address, key, Some(value)) => {
+                self.cache.insert((address, key), value);
+            }
+            JournalEntry::Storage(address, key, None) => {
+                self.cache.remove(&(address, key));
+            }
+            JournalEntry::Transient(address, key, Some(value)) => {
+                self.transient.insert((address, key), value);
+            }
+            JournalEntry::Transient(address, key, None) => {
+                self.transient.remove(&(address, key));
+            }
+            JournalEntry::AccountAccessed(address) => {
+                self.accessed_accounts.remove(&address);
+            }
+            JournalEntry::SlotAccessed(address, key) => {
+                self.accessed_slots.remove(&(address, key));
+            }
+            JournalEntry::Code(hash) => {
+                self.codes.remove(&hash);
+            }
+        }
+    }
+
+    /// Returns every non-empty account with its storage, including the
+    /// pending changes.
+    pub fn dump(&self) -> Result<StateDump, DbError> {
+        let mut addresses = BTreeSet::new();
+        for address in self.db.accounts() {
+            addresses.insert(address?);
+        }
+        addresses.extend(self.accounts.keys());
+        addresses.extend(self.cache.keys().map(|(address, _)| address));
+        let mut dump = StateDump::new();
+        for address in addresses {
+            let mut storage = BTreeMap::new();
+            for slot in self.db.storage(address) {
+                let (key, value) = slot?;
+                storage.insert(key, value);
+            }
+            for ((_, key), value) in
+                self.cache.iter().filter(|((a, _), _)| *a == address)
+            {
+                storage.insert(*key, *value);
+            }
+            storage.retain(|_, value| !value.is_zero());
+            let account = self.load_account(address)?;
+            if account.is_empty() && storage.is_empty() {
+                continue;
+            }
+            let account = AccountDump {
+                nonce: account.nonce,
+                balance: account.balance,
+                code: self.load_code(address)?,
+                storage,
+            };
+            dump.insert(address, account);
+        }
+        Ok(dump)
     }
 
     /// Reverts all the pending changes and goes back to database state.
     pub fn rollback(&mut self) {
-        self.cache.clear()
+        self.accounts.clear();
+        self.cache.clear();
+        self.codes.clear();
+        self.end_transaction();
     }
 
-    /// Commits all the pending changes to the database.
-    pub fn commit(&mut self) {
-        self.cache.drain().for_each(|(k, v)| self.db.set(k, v))
+    /// Commits all the pending changes to the database in a single batch.
+    /// On failure, none of the changes are written and all are dropped.
+    pub fn commit(&mut self) -> Result<(), DbError> {
+        let codes = self.codes.drain().map(|(_, code)| WriteOp::Code(code));
+        let accounts = self
+            .accounts
+            .drain()
+            .map(|(a, acc)| WriteOp::Account(a, acc));
+        let storage = self
+            .cache
+            .drain()
+            .map(|((a, k), v)| WriteOp::Storage(a, k, v));
+        let batch = codes.chain(accounts).chain(storage).collect();
+        let res = self.db.write_batch(batch);
+        self.end_transaction();
+        res
+    }
+
+    /// Clears the data that only lives for the duration of a transaction.
+    fn end_transaction(&mut self) {
+        self.journal.clear();
+        self.checkpoints = 0;
+        self.transient.clear();
+        self.accessed_accounts.clear();
+        self.accessed_slots.clear();
     }
 }
 
@@ -49,37 +370,246 @@ mod tests {
     #[test]
     fn test_state_load_store() {
         let mut db = MemoryDB::new();
-        db.set(123.into(), 456.into());
+        let addr = Address::zero();
+        db.set(addr, 123.into(), 456.into()).unwrap();
         let mut st = State::new(db);
-        assert_eq!(st.load(123.into()), 456.into());
-        assert_eq!(st.load(124.into()), 0.into());
-        st.store(123.into(), 457.into());
-        st.store(124.into(), 458.into());
-        assert_eq!(st.load(123.into()), 457.into());
-        assert_eq!(st.load(124.into()), 458.into());
-        assert_eq!(st.load(125.into()), 0.into());
+        assert_eq!(st.load(addr, 123.into()).unwrap(), 456.into());
+        assert_eq!(st.load(addr, 124.into()).unwrap(), 0.into());
+        st.store(addr, 123.into(), 457.into());
+        st.store(addr, 124.into(), 458.into());
+        assert_eq!(st.load(addr, 123.into()).unwrap(), 457.into());
+        assert_eq!(st.load(addr, 124.into()).unwrap(), 458.into());
+        assert_eq!(st.load(addr, 125.into()).unwrap(), 0.into());
     }
 
     #[test]
     fn test_state_rollback() {
         let mut db = MemoryDB::new();
-        db.set(123.into(), 456.into());
+        let addr = Address::zero();
+        db.set(addr, 123.into(), 456.into()).unwrap();
         let mut st = State::new(db);
-        st.store(123.into(), 457.into());
-        assert_eq!(st.load(123.into()), 457.into());
+        st.store(addr, 123.into(), 457.into());
+        assert_eq!(st.load(addr, 123.into()).unwrap(), 457.into());
         st.rollback();
-        assert_eq!(st.load(123.into()), 456.into());
+        assert_eq!(st.load(addr, 123.into()).unwrap(), 456.into());
     }
 
     #[test]
     fn test_state_commit() {
         let mut db = MemoryDB::new();
-        db.set(123.into(), 456.into());
+        let addr = Address::zero();
+        db.set(addr, 123.into(), 456.into()).unwrap();
+        let mut st = State::new(db);
+        st.store(addr, 123.into(), 457.into());
+        assert_eq!(st.load(addr, 123.into()).unwrap(), 457.into());
+        st.commit().unwrap();
+        st.rollback();
+        assert_eq!(st.load(addr, 123.into()).unwrap(), 457.into());
+    }
+
+    #[test]
+    fn test_state_checkpoint_revert() {
+        let addr = Address::zero();
+        let mut st = State::new(MemoryDB::new());
+        st.store(addr, 1.into(), 1.into());
+        let checkpoint = st.checkpoint();
+        st.store(addr, 1.into(), 2.into());
+        st.store(addr, 2.into(), 2.into());
+        st.revert_to(checkpoint);
+        assert_eq!(st.load(addr, 1.into()).unwrap(), 1.into());
+        assert_eq!(st.load(addr, 2.into()).unwrap(), 0.into());
+    }
+
+    #[test]
+    fn test_state_nested_checkpoints() {
+        let addr = Address::zero();
+        let mut st = State::new(MemoryDB::new());
+        let outer = st.checkpoint();
+        st.store(addr, 1.into(), 1.into());
+        let inner = st.checkpoint();
+        st.store(addr, 1.into(), 2.into());
+        st.store(addr, 2.into(), 2.into());
+        st.revert_to(inner);
+        assert_eq!(st.load(addr, 1.into()).unwrap(), 1.into());
+        assert_eq!(st.load(addr, 2.into()).unwrap(), 0.into());
+        let inner = st.checkpoint();
+        st.store(addr, 3.into(), 3.into());
+        st.discard(inner);
+        assert_eq!(st.load(addr, 3.into()).unwrap(), 3.into());
+        st.revert_to(outer);
+        assert_eq!(st.load(addr, 1.into()).unwrap(), 0.into());
+        assert_eq!(st.load(addr, 3.into()).unwrap(), 0.into());
+        // Changes outside of any checkpoint are not journaled.
+        st.store(addr, 4.into(), 4.into());
+        assert!(st.journal.is_empty());
+    }
+
+    #[test]
+    fn test_state_checkpoint_accounts() {
+        let addr = Address::from_low_u64_be(1);
+        let mut st = State::new(MemoryDB::new());
+        let account = Account {
+            nonce: 1,
+            ..Account::default()
+        };
+        st.store_account(addr, account.clone());
+        let outer = st.checkpoint();
+        st.store_account(addr, Account::default());
+        let inner = st.checkpoint();
+        st.store_account(addr, account.clone());
+        st.discard(inner);
+        st.revert_to(outer);
+        assert_eq!(st.load_account(addr).unwrap(), account);
+        assert!(st.journal.is_empty());
+    }
+
+    #[test]
+    fn test_state_transfer() {
+        let addr1 = Address::from_low_u64_be(1);
+        let addr2 = Address::from_low_u64_be(2);
+        let mut db = MemoryDB::new();
+        db.set_account(
+            addr1,
+            Account {
+                balance: 100.into(),
+                ..Account::default()
+            },
+        )
+        .unwrap();
         let mut st = State::new(db);
-        st.store(123.into(), 457.into());
-        assert_eq!(st.load(123.into()), 457.into());
-        st.commit();
+        assert_eq!(st.transfer(addr1, addr2, 30.into()), Ok(()));
+        assert_eq!(st.load_account(addr1).unwrap().balance, 70.into());
+        assert_eq!(st.load_account(addr2).unwrap().balance, 30.into());
+        assert_eq!(
+            st.transfer(addr1, addr2, 71.into()),
+            Err(Error::InsufficientBalance)
+        );
+        assert_eq!(st.transfer(addr1, addr1, 70.into()), Ok(()));
+        assert_eq!(st.load_account(addr1).unwrap().balance, 70.into());
+        st.rollback();
+        assert_eq!(st.load_account(addr1).unwrap().balance, 100.into());
+        assert_eq!(st.load_account(addr2).unwrap().balance, 0.into());
+    }
+
+    #[test]
+    fn test_state_accounts() {
+        let addr1 = Address::from_low_u64_be(1);
+        let addr2 = Address::from_low_u64_be(2);
+        let mut st = State::new(MemoryDB::new());
+        st.store(addr1, 123.into(), 1.into());
+        st.store(addr2, 123.into(), 2.into());
+        st.store_account(
+            addr1,
+            Account {
+                nonce: 1,
+                ..Account::default()
+            },
+        );
+        st.commit().unwrap();
+        assert_eq!(st.load(addr1, 123.into()).unwrap(), 1.into());
+        assert_eq!(st.load(addr2, 123.into()).unwrap(), 2.into());
+        assert_eq!(st.load_account(addr1).unwrap().nonce, 1);
+        assert_eq!(st.load_account(addr2).unwrap().nonce, 0);
+        st.store_account(addr2, st.load_account(addr1).unwrap());
+        st.rollback();
+        assert_eq!(st.load_account(addr2).unwrap().nonce, 0);
+    }
+
+    #[test]
+    fn test_state_transaction_scoped() {
+        let mut st = State::new(MemoryDB::new());
+        let addr = Address::zero();
+        assert!(st.access_account(addr));
+        assert!(!st.access_account(addr));
+        assert!(st.access_slot(addr, 1.into()));
+        st.tstore(addr, 1.into(), 2.into());
+        let checkpoint = st.checkpoint();
+        assert!(st.access_slot(addr, 2.into()));
+        st.tstore(addr, 1.into(), 3.into());
+        st.revert_to(checkpoint);
+        assert_eq!(st.tload(addr, 1.into()), 2.into());
+        assert!(!st.access_slot(addr, 1.into()));
+        assert!(st.access_slot(addr, 2.into()));
+        st.store(addr, 1.into(), 4.into());
+        assert_eq!(st.original(addr, 1.into()).unwrap(), 0.into());
+        st.commit().unwrap();
+        assert_eq!(st.original(addr, 1.into()).unwrap(), 4.into());
+        assert_eq!(st.tload(addr, 1.into()), 0.into());
+        assert!(st.access_account(addr));
+        assert!(st.access_slot(addr, 1.into()));
+    }
+
+    #[test]
+    fn test_state_code() {
+        let addr = Address::from_low_u64_be(1);
+        let mut st = State::new(MemoryDB::new());
+        assert_eq!(st.load_code(addr).unwrap(), Vec::<u8>::new());
+        st.store_code(addr, vec![0x60, 0x00]).unwrap();
+        assert_eq!(st.load_code(addr).unwrap(), vec![0x60, 0x00]);
         st.rollback();
-        assert_eq!(st.load(123.into()), 457.into());
+        assert_eq!(st.load_code(addr).unwrap(), Vec::<u8>::new());
+        st.store_code(addr, vec![0x60, 0x00]).unwrap();
+        st.commit().unwrap();
+        let account = st.load_account(addr).unwrap();
+        assert_eq!(account.code_hash, code_hash(&[0x60, 0x00]));
+        assert_eq!(st.load_code(addr).unwrap(), vec![0x60, 0x00]);
+        // Code is shared by hash between accounts.
+        let other = Address::from_low_u64_be(2);
+        st.store_account(other, account);
+        assert_eq!(st.load_code(other).unwrap(), vec![0x60, 0x00]);
+        let missing = Account {
+            code_hash: H256::repeat_byte(1),
+            ..Account::default()
+        };
+        st.store_account(other, missing);
+        assert_eq!(st.load_code(other), Err(DbError::Corrupted));
+    }
+
+    #[test]
+    fn test_state_checkpoint_code() {
+        let addr = Address::from_low_u64_be(1);
+        let hash = code_hash(&[0x60, 0x00]);
+        let mut st = State::new(MemoryDB::new());
+        let checkpoint = st.checkpoint();
+        st.store_code(addr, vec![0x60, 0x00]).unwrap();
+        st.revert_to(checkpoint);
+        assert_eq!(st.load_code(addr).unwrap(), Vec::<u8>::new());
+        st.commit().unwrap();
+        assert_eq!(st.db.get_code(hash).unwrap(), None);
+    }
+
+    #[test]
+    fn test_state_dump() {
+        let addr1 = Address::from_low_u64_be(1);
+        let addr2 = Address::from_low_u64_be(2);
+        let mut db = MemoryDB::new();
+        db.set(addr1, 1.into(), 1.into()).unwrap();
+        db.set(addr1, 2.into(), 2.into()).unwrap();
+        let account = Account {
+            nonce: 1,
+            balance: 5.into(),
+            code_hash: code_hash(&[0x00]),
+        };
+        db.set_account(addr1, account).unwrap();
+        db.set_code(vec![0x00]).unwrap();
+        let mut st = State::new(db);
+        st.store(addr1, 2.into(), 0.into());
+        st.store(addr2, 3.into(), 3.into());
+        st.store(Address::zero(), 4.into(), 0.into());
+        let dump = st.dump().unwrap();
+        assert_eq!(dump.len(), 2);
+        let expected = AccountDump {
+            nonce: 1,
+            balance: 5.into(),
+            code: vec![0x00],
+            storage: BTreeMap::from([(1.into(), 1.into())]),
+        };
+        assert_eq!(dump[&addr1], expected);
+        assert_eq!(
+            dump[&addr2].storage,
+            BTreeMap::from([(3.into(), 3.into())])
+        );
+        let json = serde_json::to_string(&dump).unwrap();
+        assert_eq!(serde_json::from_str::<StateDump>(&json).unwrap(), dump);
     }
 }
@@ -1,21 +1,115 @@
+use crate::abi::{AbiError, Revert};
+use crate::db::DbError;
 use ethereum_types::{Address, H256, U256};
 use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::collections::BTreeMap;
+use std::fmt;
 
-#[derive(Debug)]
+/// Keccak-256 hash of the empty code, the code hash of accounts without code.
+pub const KECCAK_EMPTY: H256 = H256([
+    0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2,
+    0xdc, 0xc7, 0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b,
+    0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+]);
+
+/// Returns the hash under which the given code is stored.
+pub fn code_hash(code: &[u8]) -> H256 {
+    H256::from_slice(&Keccak256::digest(code))
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Account {
+    pub nonce: u64,
+    pub balance: U256,
+    pub code_hash: H256,
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Self {
+            nonce: 0,
+            balance: U256::zero(),
+            code_hash: KECCAK_EMPTY,
+        }
+    }
+}
+
+impl Account {
+    /// Returns true if the account has no nonce, balance, or code.
+    pub fn is_empty(&self) -> bool {
+        self.nonce == 0 && self.balance.is_zero() && !self.has_code()
+    }
+
+    /// Returns true if the account has non-empty code.
+    pub fn has_code(&self) -> bool {
+        self.code_hash != KECCAK_EMPTY
+    }
+}
+
+/// Snapshot of an account and its storage, as produced by State::dump.
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct AccountDump {
+    pub nonce: u64,
+    pub balance: U256,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    pub code: Vec<u8>,
+    pub storage: BTreeMap<U256, U256>,
+}
+
+/// Snapshot of all the accounts in a state, keyed by address.
+pub type StateDump = BTreeMap<Address, AccountDump>;
+
+/// A log emitted by the LOG0 to LOG4 opcodes.
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Log {
     pub topics: Vec<H256>,
+    #[serde_as(as = "serde_with::hex::Hex")]
     pub data: Vec<u8>,
 }
 
-#[serde_with::serde_as]
-#[derive(Serialize, Deserialize, Debug)]
+/// The environment a transaction executes in.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
 pub struct Env {
-    pub caller: Address,
-    pub timestamp: U256,
-    pub number: U256,
     pub chainid: U256,
+    pub block: BlockEnv,
+    pub tx: TxEnv,
+}
+
+/// Values of the block the transaction is included in. Fields missing from
+/// the JSON input default to zero.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct BlockEnv {
+    pub number: U256,
+    pub coinbase: Address,
+    pub timestamp: U256,
+    pub gaslimit: U256,
+    /// Returned by DIFFICULTY before the Merge.
+    pub difficulty: U256,
+    /// Returned by PREVRANDAO since the Merge, see EIP-4399.
+    pub prevrandao: H256,
+    pub basefee: U256,
+    pub blob_basefee: U256,
+}
+
+/// Values of the transaction being executed. Fields missing from the JSON
+/// input default to zero or empty.
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct TxEnv {
+    pub caller: Address,
+    pub gasprice: U256,
+    pub gaslimit: u64,
+    pub value: U256,
     #[serde_as(as = "serde_with::hex::Hex")]
     pub calldata: Vec<u8>,
+    /// Versioned hashes of the blobs carried by the transaction, returned by
+    /// BLOBHASH, see EIP-4844.
+    pub blob_hashes: Vec<H256>,
 }
 
 #[derive(PartialEq, Debug)]
@@ -23,11 +117,92 @@ pub enum Error {
     Revert(Vec<u8>),
     InvalidOpcode(u8),
     CodeOutOfBound,
+    InvalidJump { from: usize, to: U256 },
     StackOverflow,
     StackUnderflow,
     StackValueOutOfRange,
     MemoryOverflow,
     MemoryOutOfBound,
+    ReturnDataOutOfBound,
+    OutOfGas,
+    StaticCallViolation,
+    CreateCollision,
+    CodeSizeExceeded,
+    InitcodeSizeExceeded,
+    InsufficientBalance,
+    PrecompileFailed,
+    Database(DbError),
+    Abi(AbiError),
+}
+
+impl Error {
+    /// Returns the decoded reason if the execution reverted.
+    pub fn revert(&self) -> Option<Revert> {
+        match self {
+            Error::Revert(data) => Some(Revert::decode(data)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Revert(data) => {
+                write!(f, "execution reverted: {}", Revert::decode(data))
+            }
+            Error::InvalidOpcode(op) => {
+                write!(f, "invalid opcode 0x{:02x}", op)
+            }
+            Error::CodeOutOfBound => write!(f, "code out of bound"),
+            Error::InvalidJump { from, to } => {
+                write!(f, "invalid jump from {} to {}", from, to)
+            }
+            Error::StackOverflow => write!(f, "stack overflow"),
+            Error::StackUnderflow => write!(f, "stack underflow"),
+            Error::StackValueOutOfRange => {
+                write!(f, "stack value out of range")
+            }
+            Error::MemoryOverflow => write!(f, "memory overflow"),
+            Error::MemoryOutOfBound => write!(f, "memory out of bound"),
+            Error::ReturnDataOutOfBound => {
+                write!(f, "return data out of bound")
+            }
+            Error::OutOfGas => write!(f, "out of gas"),
+            Error::StaticCallViolation => {
+                write!(f, "state change in static call")
+            }
+            Error::CreateCollision => write!(f, "contract address collision"),
+            Error::CodeSizeExceeded => write!(f, "code size exceeded"),
+            Error::InitcodeSizeExceeded => write!(f, "initcode size exceeded"),
+            Error::InsufficientBalance => write!(f, "insufficient balance"),
+            Error::PrecompileFailed => write!(f, "precompile failed"),
+            Error::Database(err) => write!(f, "{}", err),
+            Error::Abi(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(err) => Some(err),
+            Error::Abi(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DbError> for Error {
+    fn from(err: DbError) -> Self {
+        Error::Database(err)
+    }
+}
+
+impl From<AbiError> for Error {
+    fn from(err: AbiError) -> Self {
+        Error::Abi(err)
+    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -37,4 +212,57 @@ pub enum OpStep {
 }
 
 pub type OpResult = Result<OpStep, Error>;
-pub type RunResult = Result<(Vec<u8>, Vec<Log>), Error>;
+pub type ExecResult = Result<(Vec<u8>, Vec<Log>), Error>;
+
+#[derive(Debug)]
+pub struct RunResult {
+    pub result: ExecResult,
+    pub gas_used: u64,
+    pub gas_refund: u64,
+    pub gas_left: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_hash() {
+        assert_eq!(code_hash(&[]), KECCAK_EMPTY);
+        assert!(!Account::default().has_code());
+        assert!(Account::default().is_empty());
+    }
+
+    #[test]
+    fn test_error_display() {
+        let mut data = crate::abi::ERROR_SELECTOR.to_vec();
+        data.extend(crate::abi::encode(&["Nope".into()]));
+        let err = Error::Revert(data);
+        assert_eq!(err.revert(), Some(Revert::Error("Nope".into())));
+        assert_eq!(err.to_string(), "execution reverted: Nope");
+        let err = Error::Revert(vec![]);
+        assert_eq!(err.to_string(), "execution reverted: no data");
+        assert_eq!(Error::OutOfGas.revert(), None);
+        let err = Error::from(DbError::Corrupted);
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), "database value is corrupted");
+        assert_eq!(
+            Error::InvalidOpcode(0xfe).to_string(),
+            "invalid opcode 0xfe"
+        );
+    }
+
+    #[test]
+    fn test_log_serde() {
+        let log = Log {
+            topics: vec![H256::repeat_byte(0xab)],
+            data: vec![0x12, 0x34],
+        };
+        let json = serde_json::to_string(&log).unwrap();
+        assert_eq!(
+            json,
+            format!(r#"{{"topics":["0x{}"],"data":"1234"}}"#, "ab".repeat(32))
+        );
+        assert_eq!(serde_json::from_str::<Log>(&json).unwrap(), log);
+    }
+}
@@ -1,35 +1,782 @@
-use crate::db::Database;
+use crate::abi::{Function, Token};
+use crate::db::{BlockHashProvider, Database};
+use crate::gas;
+use crate::precompile::{Precompile, Precompiles};
 use crate::runtime;
+use crate::spec::Spec;
 use crate::state::State;
-use crate::types::{Env, RunResult};
+use crate::types::{Env, Error, RunResult, TxEnv};
+use ethereum_types::{Address, H160};
 
-pub struct VM<'a, DB> {
-    code: &'a [u8],
+/// Address of the contract serving past block hashes, see EIP-2935.
+pub const HISTORY_STORAGE_ADDRESS: Address = H160([
+    0x00, 0x00, 0xf9, 0x08, 0x27, 0xf1, 0xc5, 0x3a, 0x10, 0xcb, 0x7a, 0x02,
+    0x33, 0x5b, 0x17, 0x53, 0x20, 0x00, 0x29, 0x35,
+]);
+
+/// Caller of the system calls made at the start of a block.
+pub const SYSTEM_ADDRESS: Address = H160([
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+]);
+
+const SYSTEM_CALL_GAS: u64 = 30_000_000;
+
+pub struct VM<DB> {
+    address: Address,
     state: State<DB>,
+    precompiles: Precompiles,
+    spec: Spec,
 }
 
-impl<'a, DB: Database> VM<'a, DB> {
-    pub fn new(db: DB, code: &'a [u8]) -> Self {
-        Self {
-            code,
-            state: State::new(db),
+/// Configures a VM before creating it. By default, the VM runs the code at
+/// the zero address under the latest fork with the built-in precompiles.
+pub struct VMBuilder<DB> {
+    db: DB,
+    address: Address,
+    precompiles: Precompiles,
+    block_hashes: Option<Box<dyn BlockHashProvider>>,
+    spec: Spec,
+}
+
+impl<DB: Database> VMBuilder<DB> {
+    /// Sets the address of the code that transactions run.
+    pub fn address(mut self, address: Address) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Sets the fork whose rules the VM follows.
+    pub fn spec(mut self, spec: Spec) -> Self {
+        self.spec = spec;
+        self
+    }
+
+    /// Installs a custom precompile, see VM::register_precompile.
+    pub fn precompile(
+        mut self,
+        address: Address,
+        precompile: impl Precompile + 'static,
+    ) -> Self {
+        self.precompiles.insert(address, precompile);
+        self
+    }
+
+    /// Sets the source of past block hashes for BLOCKHASH and the EIP-2935
+    /// history. Without one, every past block hash is zero.
+    pub fn block_hashes(
+        mut self,
+        provider: impl BlockHashProvider + 'static,
+    ) -> Self {
+        self.block_hashes = Some(Box::new(provider));
+        self
+    }
+
+    pub fn build(self) -> VM<DB> {
+        let mut state = State::new(self.db);
+        if let Some(provider) = self.block_hashes {
+            state.set_block_hashes(provider);
+        }
+        VM {
+            address: self.address,
+            state,
+            precompiles: self.precompiles,
+            spec: self.spec,
+        }
+    }
+}
+
+impl<DB: Database> VM<DB> {
+    /// Returns a builder of a VM on top of the given database.
+    pub fn builder(db: DB) -> VMBuilder<DB> {
+        VMBuilder {
+            db,
+            address: Address::zero(),
+            precompiles: Precompiles::new(),
+            block_hashes: None,
+            spec: Spec::LATEST,
+        }
+    }
+
+    /// Creates a VM running the code stored at the given address under the
+    /// rules of the given fork.
+    pub fn new(db: DB, address: Address, spec: Spec) -> Self {
+        Self::builder(db).address(address).spec(spec).build()
+    }
+
+    /// Returns the state of the accounts, including the changes of the
+    /// transactions run so far.
+    pub fn state(&self) -> &State<DB> {
+        &self.state
+    }
+
+    /// Returns the state of the accounts, e.g. to fund accounts before
+    /// running transactions. Changes are written by State::commit.
+    pub fn state_mut(&mut self) -> &mut State<DB> {
+        &mut self.state
+    }
+
+    /// Installs a custom precompile at the given address, replacing the
+    /// built-in one or the code of the account there if any.
+    pub fn register_precompile(
+        &mut self,
+        address: Address,
+        precompile: impl Precompile + 'static,
+    ) {
+        self.precompiles.insert(address, precompile);
+    }
+
+    /// Sets the code of the account at the given address and writes it to
+    /// the database right away, outside of any transaction.
+    pub fn set_code(
+        &mut self,
+        address: Address,
+        code: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.state.store_code(address, code)?;
+        Ok(self.state.commit()?)
+    }
+
+    /// Prepares the state for the block of the given environment. Since
+    /// Prague, this stores the parent block hash in the history contract.
+    pub fn begin_block(&mut self, env: &Env) -> Result<(), Error> {
+        if !self.spec.enabled(Spec::Prague) || env.block.number.is_zero() {
+            return Ok(());
+        }
+        let code = self.state.load_code(HISTORY_STORAGE_ADDRESS)?;
+        if code.is_empty() {
+            return Ok(());
+        }
+        let parent = (env.block.number - 1).low_u64();
+        let hash = self.state.block_hash(parent)?;
+        let env = Env {
+            chainid: env.chainid,
+            block: env.block.clone(),
+            tx: TxEnv {
+                caller: SYSTEM_ADDRESS,
+                gaslimit: SYSTEM_CALL_GAS,
+                calldata: hash.as_bytes().to_vec(),
+                ..TxEnv::default()
+            },
+        };
+        let address = HISTORY_STORAGE_ADDRESS;
+        let state = &mut self.state;
+        let mut res = runtime::run(
+            address,
+            &code,
+            state,
+            &env,
+            &self.precompiles,
+            self.spec,
+            SYSTEM_CALL_GAS,
+        );
+        self.finalize(&mut res);
+        match res.result {
+            Err(err @ Error::Database(_)) => Err(err),
+            _ => Ok(()),
         }
     }
 
     /// Runs a transaction and returns the result + updates the state.
     pub fn run(&mut self, env: &Env) -> RunResult {
-        let res = runtime::run(self.code, &mut self.state, env);
-        match res {
-            Ok(_) => self.state.commit(),
-            Err(_) => self.state.rollback(),
-        }
+        let mut res = self.execute(env);
+        self.finalize(&mut res);
         res
     }
 
     /// Runs a transaction and returns the result + discards state changes.
     pub fn call(&mut self, env: &Env) -> RunResult {
-        let res = runtime::run(self.code, &mut self.state, env);
+        let res = self.execute(env);
         self.state.rollback();
         res
     }
+
+    /// Runs a transaction calling the function with the given signature and
+    /// arguments, and returns the decoded outputs + updates the state. The
+    /// output types follow the signature as in "getX()(uint256)".
+    pub fn run_fn(
+        &mut self,
+        env: &Env,
+        signature: &str,
+        args: &[Token],
+    ) -> Result<Vec<Token>, Error> {
+        let (function, env) = encode_call(env, signature, args)?;
+        let (output, _) = self.run(&env).result?;
+        Ok(function.decode_output(&output)?)
+    }
+
+    /// Calls the function with the given signature and arguments, and
+    /// returns the decoded outputs + discards state changes.
+    pub fn call_fn(
+        &mut self,
+        env: &Env,
+        signature: &str,
+        args: &[Token],
+    ) -> Result<Vec<Token>, Error> {
+        let (function, env) = encode_call(env, signature, args)?;
+        let (output, _) = self.call(&env).result?;
+        Ok(function.decode_output(&output)?)
+    }
+
+    /// Runs a deployment transaction with the given init code and returns the
+    /// address of the new contract + the result + updates the state.
+    pub fn deploy(
+        &mut self,
+        env: &Env,
+        initcode: &[u8],
+    ) -> (Address, RunResult) {
+        let mut sender = match self.state.load_account(env.tx.caller) {
+            Ok(sender) => sender,
+            Err(err) => return (Address::zero(), invalid(env, err.into())),
+        };
+        let address = runtime::create_address(env.tx.caller, sender.nonce);
+        let intrinsic = gas::intrinsic_create(self.spec, initcode);
+        if intrinsic > env.tx.gaslimit {
+            return (address, out_of_gas(env));
+        }
+        sender.nonce += 1;
+        self.state.store_account(env.tx.caller, sender);
+        // The nonce increment stays even if the deployment fails.
+        let checkpoint = self.state.checkpoint();
+        if let Err(err) =
+            self.state.transfer(env.tx.caller, address, env.tx.value)
+        {
+            self.state.revert_to(checkpoint);
+            self.state.rollback();
+            return (address, invalid(env, err));
+        }
+        let limit = env.tx.gaslimit - intrinsic;
+        let state = &mut self.state;
+        let precompiles = &self.precompiles;
+        let res = runtime::deploy(
+            address,
+            initcode,
+            state,
+            env,
+            precompiles,
+            self.spec,
+            limit,
+        );
+        let mut res = with_intrinsic(res, env, self.spec, intrinsic);
+        match res.result {
+            Ok(_) => self.state.discard(checkpoint),
+            Err(Error::Database(_)) => {
+                self.state.revert_to(checkpoint);
+                self.state.rollback();
+                return (address, res);
+            }
+            Err(_) => self.state.revert_to(checkpoint),
+        }
+        if let Err(err) = self.state.commit() {
+            res.result = Err(err.into());
+        }
+        (address, res)
+    }
+
+    /// Charges the intrinsic gas and executes the code against the state.
+    fn execute(&mut self, env: &Env) -> RunResult {
+        let intrinsic = gas::intrinsic(self.spec, &env.tx.calldata);
+        if intrinsic > env.tx.gaslimit {
+            return out_of_gas(env);
+        }
+        let code = match self.state.load_code(self.address) {
+            Ok(code) => code,
+            Err(err) => return invalid(env, err.into()),
+        };
+        let value = env.tx.value;
+        if let Err(err) =
+            self.state.transfer(env.tx.caller, self.address, value)
+        {
+            return invalid(env, err);
+        }
+        let limit = env.tx.gaslimit - intrinsic;
+        let state = &mut self.state;
+        let precompiles = &self.precompiles;
+        let res = runtime::run(
+            self.address,
+            &code,
+            state,
+            env,
+            precompiles,
+            self.spec,
+            limit,
+        );
+        with_intrinsic(res, env, self.spec, intrinsic)
+    }
+
+    /// Commits the state changes of a successful transaction, or drops them.
+    /// A failed commit turns the result into a database error.
+    fn finalize(&mut self, res: &mut RunResult) {
+        match res.result {
+            Ok(_) => {
+                if let Err(err) = self.state.commit() {
+                    res.result = Err(err.into());
+                }
+            }
+            Err(_) => self.state.rollback(),
+        }
+    }
+}
+
+/// Returns the function with the given signature and a copy of the
+/// environment calling it with the given arguments.
+fn encode_call(
+    env: &Env,
+    signature: &str,
+    args: &[Token],
+) -> Result<(Function, Env), Error> {
+    let function = Function::parse(signature)?;
+    let mut env = env.clone();
+    env.tx.calldata = function.encode_input(args)?;
+    Ok((function, env))
+}
+
+/// Returns the result of a transaction that cannot pay its intrinsic gas.
+fn out_of_gas(env: &Env) -> RunResult {
+    RunResult {
+        result: Err(Error::OutOfGas),
+        gas_used: env.tx.gaslimit,
+        gas_refund: 0,
+        gas_left: 0,
+    }
+}
+
+/// Returns the result of a transaction rejected before any execution.
+fn invalid(env: &Env, err: Error) -> RunResult {
+    RunResult {
+        result: Err(err),
+        gas_used: 0,
+        gas_refund: 0,
+        gas_left: env.tx.gaslimit,
+    }
+}
+
+/// Adds the intrinsic gas to the execution gas and applies the refund.
+fn with_intrinsic(
+    mut res: RunResult,
+    env: &Env,
+    spec: Spec,
+    intrinsic: u64,
+) -> RunResult {
+    let used = res.gas_used + intrinsic;
+    res.gas_used = gas::apply_refund(spec, used, res.gas_refund);
+    res.gas_left = env.tx.gaslimit - res.gas_used;
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::Revert;
+    use crate::db::{DbError, MemoryDB};
+    use crate::gas::Gas;
+    use crate::precompile::PrecompileCall;
+    use crate::types::{code_hash, Account, BlockEnv};
+    use ethereum_types::{H256, U256};
+
+    fn test_env(value: u64) -> Env {
+        Env {
+            chainid: 1.into(),
+            block: BlockEnv::default(),
+            tx: TxEnv {
+                caller: Address::from_low_u64_be(0xca11),
+                gaslimit: 1_000_000,
+                value: value.into(),
+                ..TxEnv::default()
+            },
+        }
+    }
+
+    fn funded_db(address: Address, balance: u64) -> MemoryDB {
+        let mut db = MemoryDB::new();
+        let account = Account {
+            balance: balance.into(),
+            ..Account::default()
+        };
+        db.set_account(address, account).unwrap();
+        db
+    }
+
+    #[test]
+    fn test_vm_builder() {
+        let env = test_env(0);
+        let address = Address::from_low_u64_be(0xc0de);
+        let precompile = Address::from_low_u64_be(0x0100);
+        let mut vm = VM::builder(MemoryDB::new())
+            .address(address)
+            .spec(Spec::Berlin)
+            .precompile(precompile, |_: &PrecompileCall, _: &mut Gas| {
+                Ok(vec![1])
+            })
+            .build();
+        assert_eq!(vm.spec, Spec::Berlin);
+        // PUSH0 is not available before Shanghai.
+        vm.state_mut().store_code(address, vec![0x5f]).unwrap();
+        vm.state_mut().commit().unwrap();
+        assert_eq!(vm.state().load_code(address).unwrap(), vec![0x5f]);
+        assert_eq!(vm.run(&env).result, Err(Error::InvalidOpcode(0x5f)));
+        vm.set_code(address, hex::decode(CUSTOM_CALLER).unwrap())
+            .unwrap();
+        let (output, _) = vm.run(&env).result.unwrap();
+        assert_eq!(output[0], 1);
+    }
+
+    #[test]
+    fn test_vm_value_transfer() {
+        let env = test_env(40);
+        let db = funded_db(env.tx.caller, 100);
+        // CALLVALUE, PUSH1 0, SSTORE, STOP
+        let code = hex::decode("3460005500").unwrap();
+        let address = Address::from_low_u64_be(0xc0de);
+        let mut vm = VM::new(db, address, Spec::LATEST);
+        vm.set_code(address, code).unwrap();
+        assert!(vm.run(&env).result.is_ok());
+        assert_eq!(
+            vm.state.load_account(env.tx.caller).unwrap().balance,
+            60.into()
+        );
+        assert_eq!(vm.state.load_account(address).unwrap().balance, 40.into());
+        assert_eq!(vm.state.load(address, 0.into()).unwrap(), 40.into());
+        assert!(vm.call(&env).result.is_ok());
+        assert_eq!(
+            vm.state.load_account(env.tx.caller).unwrap().balance,
+            60.into()
+        );
+    }
+
+    #[test]
+    fn test_vm_value_transfer_without_code() {
+        let env = test_env(40);
+        let db = funded_db(env.tx.caller, 100);
+        let address = Address::from_low_u64_be(0xbeef);
+        let mut vm = VM::new(db, address, Spec::LATEST);
+        let res = vm.run(&env);
+        assert_eq!(res.result, Ok((vec![], vec![])));
+        assert_eq!(res.gas_used, 21000);
+        vm.state.rollback();
+        assert_eq!(
+            vm.state.load_account(env.tx.caller).unwrap().balance,
+            60.into()
+        );
+        assert_eq!(vm.state.load_account(address).unwrap().balance, 40.into());
+    }
+
+    #[test]
+    fn test_vm_call_with_value_without_code() {
+        let env = test_env(0);
+        let address = Address::from_low_u64_be(0xc0de);
+        let db = funded_db(address, 150);
+        // CALL(0xffff, 0xbeef, 100, 0, 0, 0, 0) and return the success flag.
+        let code = "6000600060006000606461beef61fffff160005260206000f3";
+        let code = hex::decode(code).unwrap();
+        let mut vm = VM::new(db, address, Spec::LATEST);
+        vm.set_code(address, code).unwrap();
+        let (output, _) = vm.run(&env).result.unwrap();
+        assert_eq!(output[31], 1);
+        vm.state.rollback();
+        let recipient = Address::from_low_u64_be(0xbeef);
+        assert_eq!(
+            vm.state.load_account(recipient).unwrap().balance,
+            100.into()
+        );
+        assert_eq!(vm.state.load_account(address).unwrap().balance, 50.into());
+    }
+
+    #[test]
+    fn test_vm_insufficient_balance() {
+        let env = test_env(101);
+        let db = funded_db(env.tx.caller, 100);
+        let address = Address::from_low_u64_be(0xc0de);
+        let mut vm = VM::new(db, address, Spec::LATEST);
+        let res = vm.run(&env);
+        assert_eq!(res.result, Err(Error::InsufficientBalance));
+        assert_eq!(res.gas_used, 0);
+        assert_eq!(
+            vm.state.load_account(env.tx.caller).unwrap().balance,
+            100.into()
+        );
+    }
+
+    #[test]
+    fn test_vm_deploy() {
+        let env = test_env(5);
+        let db = funded_db(env.tx.caller, 100);
+        let mut vm = VM::new(db, Address::zero(), Spec::LATEST);
+        // Init code returning the single byte STOP as runtime code.
+        let initcode = hex::decode("600060005360016000f3").unwrap();
+        let (address, res) = vm.deploy(&env, &initcode);
+        assert_eq!(res.result, Ok((vec![0], vec![])));
+        assert_eq!(address, runtime::create_address(env.tx.caller, 0));
+        assert_eq!(vm.state.load_code(address).unwrap(), vec![0]);
+        let account = vm.state.load_account(address).unwrap();
+        assert_eq!(account.balance, 5.into());
+        assert_eq!(vm.state.load_account(env.tx.caller).unwrap().nonce, 1);
+        let (next, _) = vm.deploy(&env, &initcode);
+        assert_eq!(next, runtime::create_address(env.tx.caller, 1));
+    }
+
+    #[test]
+    fn test_vm_deploy_failure_keeps_nonce() {
+        let env = test_env(5);
+        let db = funded_db(env.tx.caller, 100);
+        let mut vm = VM::new(db, Address::zero(), Spec::LATEST);
+        // PUSH1 0, PUSH1 0, REVERT
+        let initcode = hex::decode("60006000fd").unwrap();
+        let (address, res) = vm.deploy(&env, &initcode);
+        assert_eq!(res.result, Err(Error::Revert(vec![])));
+        assert_eq!(address, runtime::create_address(env.tx.caller, 0));
+        let sender = vm.state.load_account(env.tx.caller).unwrap();
+        assert_eq!(sender.nonce, 1);
+        assert_eq!(sender.balance, 100.into());
+        assert!(vm.state.load_account(address).unwrap().is_empty());
+        let (next, _) = vm.deploy(&env, &initcode);
+        assert_eq!(next, runtime::create_address(env.tx.caller, 1));
+        assert_eq!(vm.state.load_account(env.tx.caller).unwrap().nonce, 2);
+    }
+
+    // CALL 0x0100 with no input, MSTORE the flag after the first output
+    // word and return both.
+    const CUSTOM_CALLER: &str =
+        "6020600060006000600061010061fffff160205260406000f3";
+
+    #[test]
+    fn test_vm_custom_precompile() {
+        let mut env = test_env(0);
+        env.block.number = 7.into();
+        let address = Address::from_low_u64_be(0xc0de);
+        let mut vm = VM::new(MemoryDB::new(), address, Spec::LATEST);
+        vm.set_code(address, hex::decode(CUSTOM_CALLER).unwrap())
+            .unwrap();
+        let precompile = Address::from_low_u64_be(0x0100);
+        vm.register_precompile(
+            precompile,
+            |call: &PrecompileCall, gas: &mut Gas| {
+                gas.charge(1000)?;
+                let mut output = H256::from(call.caller).as_bytes().to_vec();
+                output[0] = call.env.block.number.low_u64() as u8;
+                Ok(output)
+            },
+        );
+        let res = vm.run(&env);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(output[0], 7);
+        assert_eq!(Address::from_slice(&output[12..32]), address);
+        assert_eq!(U256::from_big_endian(&output[32..]), 1.into());
+        let used = res.gas_used;
+        vm.register_precompile(
+            precompile,
+            |_: &PrecompileCall, _: &mut Gas| {
+                Err(Error::Revert(b"no".to_vec()))
+            },
+        );
+        let res = vm.run(&env);
+        let (output, _) = res.result.unwrap();
+        assert_eq!(&output[..2], b"no");
+        assert_eq!(U256::from_big_endian(&output[32..]), 0.into());
+        // Reverting keeps the unspent gas, unlike other failures.
+        assert_eq!(res.gas_used, used - 1000);
+        vm.register_precompile(
+            precompile,
+            |_: &PrecompileCall, _: &mut Gas| Err(Error::PrecompileFailed),
+        );
+        let res = vm.run(&env);
+        assert_eq!(res.gas_used, used - 1000 + 0xffff);
+    }
+
+    // Runtime code of a contract with a public uint256 x, where add_x(v) adds
+    // v to x and emits an event.
+    const COUNTER_CODE: &str = concat!(
+        "608060405234801561001057600080fd5b50600436106100575760003560e01c",
+        "80630a8e8e011461005c5780630c55699c1461009257806366e41cb71461009b",
+        "578063980cd0fc146100a3578063f8a8fd6d146100b8575b600080fd5b604080",
+        "5160016020808301919091528251808303820181529183019092528051910120",
+        "5b60405190815260200160405180910390f35b61008060005481565b61008061",
+        "00bf565b6100b66100b136600461014d565b6100f4565b005b602a610080565b",
+        "60405162461bcd60e51b815260206004820152600360248201526209cc2d60eb",
+        "1b604482015260009060640160405180910390fd5b8060008082825461010591",
+        "9061017c565b90915550506000547f7afbe4f1c55b5f72ea356f5b4d56158318",
+        "67af31454a5ca5557f315e6d11a369610139826002610194565b604051908152",
+        "60200160405180910390a250565b60006020828403121561015f57600080fd5b",
+        "5035919050565b634e487b7160e01b600052601160045260246000fd5b600082",
+        "1982111561018f5761018f610166565b500190565b6000816000190483118215",
+        "1516156101ae576101ae610166565b50029056fea2646970667358221220c41b",
+        "85ba8877c9796fab15e49e19a8ed9fe5555caf085bc32e68848d65fa9e456473",
+        "6f6c634300080b0033",
+    );
+
+    #[test]
+    fn test_vm_call_fn() {
+        use crate::abi::AbiError;
+        let env = test_env(0);
+        let address = Address::from_low_u64_be(0xc0de);
+        let mut vm = VM::new(MemoryDB::new(), address, Spec::LATEST);
+        vm.set_code(address, hex::decode(COUNTER_CODE).unwrap())
+            .unwrap();
+        let res = vm.run_fn(&env, "add_x(uint256)", &[42.into()]);
+        assert_eq!(res, Ok(vec![]));
+        let res = vm.call_fn(&env, "x()(uint256)", &[]);
+        assert_eq!(res, Ok(vec![Token::Uint(42.into())]));
+        let res = vm.call_fn(&env, "add_x(uint256)", &[8.into()]);
+        assert_eq!(res, Ok(vec![]));
+        let res = vm.call_fn(&env, "x()(uint256)", &[]);
+        assert_eq!(res, Ok(vec![Token::Uint(42.into())]));
+        let res = vm.call_fn(&env, "add_x(uint256)", &[]);
+        assert_eq!(res, Err(Error::Abi(AbiError::ArgumentMismatch)));
+        let res = vm.call_fn(&env, "x()(string)", &[]);
+        assert_eq!(res, Err(Error::Abi(AbiError::InvalidData)));
+        let res = vm.call_fn(&env, "getX()", &[]);
+        assert_eq!(res.unwrap_err().to_string(), "execution reverted: no data");
+        let mut raw = env.clone();
+        raw.tx.calldata = hex::decode("66e41cb7").unwrap();
+        let err = vm.call(&raw).result.unwrap_err();
+        assert_eq!(err.to_string(), "execution reverted: Nah");
+        let err = vm
+            .call_fn(&env, "add_x(uint256)", &[U256::MAX.into()])
+            .unwrap_err();
+        assert_eq!(err.revert(), Some(Revert::Panic(0x11.into())));
+    }
+
+    #[test]
+    fn test_vm_decode_log() {
+        use crate::abi::Abi;
+        let abi = Abi::from_json(
+            r#"[{
+                "type": "event",
+                "name": "Add",
+                "inputs": [
+                    {"name": "x", "type": "uint256", "indexed": true},
+                    {"name": "doubled", "type": "uint256"}
+                ]
+            }]"#,
+        )
+        .unwrap();
+        let mut env = test_env(0);
+        let address = Address::from_low_u64_be(0xc0de);
+        let mut vm = VM::new(MemoryDB::new(), address, Spec::LATEST);
+        vm.set_code(address, hex::decode(COUNTER_CODE).unwrap())
+            .unwrap();
+        let function = Function::parse("add_x(uint256)").unwrap();
+        env.tx.calldata = function.encode_input(&[21.into()]).unwrap();
+        let (_, logs) = vm.run(&env).result.unwrap();
+        let decoded = abi.decode_log(&logs[0]).unwrap();
+        assert_eq!(decoded.name, "Add");
+        assert_eq!(
+            decoded.params,
+            vec![
+                ("x".to_string(), Token::Uint(21.into())),
+                ("doubled".to_string(), Token::Uint(42.into())),
+            ]
+        );
+    }
+
+    #[cfg(feature = "leveldb")]
+    #[test]
+    fn test_vm_code_persisted() {
+        use crate::db::LevelDB;
+        let dir = tempdir::TempDir::new("vmtest").unwrap();
+        let env = test_env(0);
+        let address = Address::from_low_u64_be(0xc0de);
+        // PUSH1 42, PUSH1 0, MSTORE, return the word
+        let code = hex::decode("602a60005260206000f3").unwrap();
+        {
+            let db = LevelDB::new(dir.path()).unwrap();
+            let mut vm = VM::new(db, address, Spec::LATEST);
+            vm.set_code(address, code).unwrap();
+        }
+        let db = LevelDB::new(dir.path()).unwrap();
+        let mut vm = VM::new(db, address, Spec::LATEST);
+        let (output, _) = vm.run(&env).result.unwrap();
+        assert_eq!(U256::from_big_endian(&output), 42.into());
+    }
+
+    // Runtime code of the EIP-2935 history storage contract.
+    const HISTORY_CODE: &str = concat!(
+        "3373fffffffffffffffffffffffffffffffffffffffe146046576020360360425",
+        "75f35600143038111604257611fff81430311604257611fff9006545f5260205f",
+        "f35b5f5ffd5b5f35611fff60014303065500"
+    );
+
+    #[test]
+    fn test_vm_block_hash_history() {
+        let code = hex::decode(HISTORY_CODE).unwrap();
+        let mut db = MemoryDB::new();
+        let account = Account {
+            code_hash: code_hash(&code),
+            ..Account::default()
+        };
+        db.set_account(HISTORY_STORAGE_ADDRESS, account).unwrap();
+        db.set_code(code).unwrap();
+        let mut hashes = MemoryDB::new();
+        hashes.set_block_hash(4, H256::repeat_byte(4));
+        let mut env = test_env(0);
+        env.block.number = 5.into();
+        let address = HISTORY_STORAGE_ADDRESS;
+        let mut vm = VM::builder(db)
+            .address(address)
+            .spec(Spec::Prague)
+            .block_hashes(hashes)
+            .build();
+        vm.begin_block(&env).unwrap();
+        let slot = U256::from(4);
+        let hash = H256::repeat_byte(4);
+        assert_eq!(
+            vm.state.load(address, slot).unwrap(),
+            U256::from(hash.as_bytes())
+        );
+        env.tx.calldata = H256::from_low_u64_be(4).as_bytes().to_vec();
+        let (output, _) = vm.call(&env).result.unwrap();
+        assert_eq!(output, hash.as_bytes());
+        // The current block is outside of the served range.
+        env.tx.calldata = H256::from_low_u64_be(5).as_bytes().to_vec();
+        assert_eq!(vm.call(&env).result, Err(Error::Revert(vec![])));
+    }
+
+    /// A block hash provider backed by the host rather than a database.
+    struct Chain;
+
+    impl BlockHashProvider for Chain {
+        fn block_hash(&self, number: u64) -> Result<Option<H256>, DbError> {
+            Ok(Some(H256::from_low_u64_be(number + 100)))
+        }
+    }
+
+    #[test]
+    fn test_vm_block_hashes() {
+        let mut env = test_env(0);
+        env.block.number = 5.into();
+        // BLOCKHASH(4), return the word
+        let code = hex::decode("60044060005260206000f3").unwrap();
+        let mut vm = VM::new(MemoryDB::new(), Address::zero(), Spec::LATEST);
+        vm.set_code(Address::zero(), code.clone()).unwrap();
+        let (output, _) = vm.call(&env).result.unwrap();
+        assert_eq!(output, H256::zero().as_bytes());
+        let mut vm = VM::builder(MemoryDB::new()).block_hashes(Chain).build();
+        vm.set_code(Address::zero(), code).unwrap();
+        let (output, _) = vm.call(&env).result.unwrap();
+        assert_eq!(output, H256::from_low_u64_be(104).as_bytes());
+    }
+
+    #[test]
+    fn test_vm_block_hash_history_before_prague() {
+        let code = hex::decode(HISTORY_CODE).unwrap();
+        let mut db = MemoryDB::new();
+        let account = Account {
+            code_hash: code_hash(&code),
+            ..Account::default()
+        };
+        db.set_account(HISTORY_STORAGE_ADDRESS, account).unwrap();
+        db.set_code(code).unwrap();
+        let mut hashes = MemoryDB::new();
+        hashes.set_block_hash(4, H256::repeat_byte(4));
+        let mut env = test_env(0);
+        env.block.number = 5.into();
+        let address = HISTORY_STORAGE_ADDRESS;
+        let mut vm = VM::builder(db)
+            .address(address)
+            .spec(Spec::Cancun)
+            .block_hashes(hashes)
+            .build();
+        vm.begin_block(&env).unwrap();
+        assert_eq!(vm.state.load(address, 4.into()).unwrap(), 0.into());
+    }
 }